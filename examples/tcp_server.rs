@@ -14,17 +14,22 @@ pub fn main() {
             println!("Accepted connection from {:?}", addr);
 
             uring_test::spawn(async move {
-                let mut buf = [0u8; 1024];
+                let mut buf = Vec::with_capacity(1024);
 
                 loop {
-                    let n = stream.read(&mut buf).await.unwrap();
+                    let (res, read_buf) = stream.read(buf).await;
+                    let n = res.unwrap();
 
                     if n == 0 {
                         println!("Connection closed by {:?}", addr);
                         break;
                     }
 
-                    stream.write(&buf[..n]).await.unwrap();
+                    let (res, mut write_buf) = stream.write(read_buf).await;
+                    res.unwrap();
+
+                    write_buf.clear();
+                    buf = write_buf;
                 }
             });
         }
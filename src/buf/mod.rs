@@ -0,0 +1,154 @@
+//! Buffers which can be handed to the kernel for IO
+//! 
+//! The runtime takes ownership of a buffer for the whole duration of an operation, and hands it back once
+//! the operation completes. If the operation's future is dropped before that, the runtime keeps the buffer
+//! alive until the kernel is done with it, so the kernel never writes into freed memory.
+
+mod pool;
+mod ring;
+mod slice;
+
+pub use pool::{BufferPool, FixedBuf};
+pub use slice::Slice;
+
+use std::ops::RangeBounds;
+pub use ring::{BufRing, ProvidedBuf};
+pub (crate) use ring::{RingShared, recycle_buffer, release_ring};
+
+/// A buffer the kernel can read from
+/// 
+/// # Safety
+/// The memory pointed to by [`stable_ptr()`](IoBuf::stable_ptr) must stay valid, and must not move
+/// even if the buffer itself is moved, until the buffer is dropped. This is why stack arrays can't be used.
+/// 
+/// If [`fixed_index()`](IoBuf::fixed_index) returns an index, the buffer must lie within the registered buffer at that index.
+pub unsafe trait IoBuf: 'static {
+    /// Pointer to the start of the buffer
+    fn stable_ptr(&self) -> *const u8;
+
+    /// Number of initialized bytes, this is how many bytes get written out of the buffer
+    fn bytes_init(&self) -> usize;
+
+    /// Total size of the buffer, including uninitialized memory
+    fn bytes_total(&self) -> usize;
+
+    /// Index of the buffer among the buffers registered with the ring, if it's one of them,
+    /// IO on registered buffers uses the fixed variants of the read and write opcodes
+    fn fixed_index(&self) -> Option<u16> {
+        None
+    }
+
+    /// Takes a range of the buffer, e.g. to continue reading into or writing out of it where an operation stopped
+    /// 
+    /// # Panics
+    /// If the range goes past [`bytes_total()`](IoBuf::bytes_total)
+    fn slice(self, range: impl RangeBounds<usize>) -> Slice<Self> where Self: Sized {
+        Slice::new(self, range)
+    }
+}
+
+/// A buffer the kernel can write into
+/// 
+/// # Safety
+/// Same as [`IoBuf`], the memory must also be valid for writes of [`bytes_total()`](IoBuf::bytes_total) bytes.
+pub unsafe trait IoBufMut: IoBuf {
+    /// Pointer to the start of the buffer
+    fn stable_mut_ptr(&mut self) -> *mut u8;
+
+    /// Marks the first `pos` bytes of the buffer as initialized, if they weren't already
+    /// 
+    /// # Safety
+    /// The first `pos` bytes must have been initialized
+    unsafe fn set_init(&mut self, pos: usize);
+}
+
+unsafe impl IoBuf for Vec<u8> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
+
+unsafe impl IoBufMut for Vec<u8> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        if self.len() < pos {
+            self.set_len(pos);
+        }
+    }
+}
+
+unsafe impl IoBuf for Box<[u8]> {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for Box<[u8]> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    // A boxed slice is always fully initialized
+    unsafe fn set_init(&mut self, _pos: usize) {}
+}
+
+unsafe impl IoBuf for &'static [u8] {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for &'static str {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for String {
+    fn stable_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.capacity()
+    }
+}
@@ -0,0 +1,225 @@
+use std::io;
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::ops::{Deref, DerefMut};
+use std::future::poll_fn;
+use std::task::{Poll, Waker};
+use std::alloc::{self, Layout};
+
+use super::{IoBuf, IoBufMut};
+use crate::RUNTIME;
+
+/// Most buffers the kernel accepts in a single registration
+const MAX_BUFFERS: u16 = 16384;
+
+/// Memory of the registered buffers, kept alive until the pool and all of its leased buffers are dropped
+struct Shared {
+    memory: *mut u8,
+    layout: Layout,
+    buf_size: usize,
+    free: RefCell<Vec<u16>>,
+    waiters: RefCell<Vec<Waker>>,
+
+    // ID of the platform the buffers are registered with
+    plat: u64,
+
+    // Cleared once the pool is dropped, so that leftover buffers stop using the fixed opcodes
+    registered: Cell<bool>
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.memory, self.layout) };
+    }
+}
+
+/// A set of buffers registered with the runtime's ring, IO on them skips pinning their pages on every operation
+/// 
+/// Buffers are leased out as [`FixedBuf`]s, which go back to the pool when dropped.
+/// Only one pool can be registered with a runtime at a time, the buffers are unregistered once the pool is dropped.
+pub struct BufferPool {
+    shared: Rc<Shared>
+}
+
+impl BufferPool {
+    /// Allocates `count` buffers of `buf_size` bytes each and registers them with the runtime's ring
+    pub fn new(count: u16, buf_size: usize) -> io::Result<Self> {
+        if count == 0 || count > MAX_BUFFERS || buf_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid number or size of buffers"));
+        }
+
+        // Only the start of the memory is page aligned, buffers are laid out back to back after it
+        let layout = (count as usize).checked_mul(buf_size)
+            .and_then(|size| Layout::from_size_align(size, 4096).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "buffers are too large"))?;
+
+        // Zeroed so that buffers are always fully initialized
+        let memory = unsafe { alloc::alloc_zeroed(layout) };
+
+        if memory.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+
+        let mut shared = Shared {
+            memory,
+            layout,
+            buf_size,
+            free: RefCell::new((0..count).rev().collect()),
+            waiters: RefCell::new(Vec::new()),
+            plat: 0,
+            registered: Cell::new(false)
+        };
+
+        let iovecs: Vec<_> = (0..count as usize)
+            .map(|index| libc::iovec {
+                iov_base: unsafe { memory.add(index * buf_size) } as *mut _,
+                iov_len: buf_size
+            })
+            .collect();
+
+        // The memory stays alive until the pool is dropped, which unregisters it
+        let plat = RUNTIME.with_borrow_mut(|rt| unsafe {
+            rt.plat.register_buffers(&iovecs).map(|_| rt.plat.id)
+        });
+
+        shared.plat = plat?;
+        shared.registered.set(true);
+
+        Ok(Self { shared: Rc::new(shared) })
+    }
+
+    /// Size of each buffer
+    pub fn buf_size(&self) -> usize {
+        self.shared.buf_size
+    }
+
+    /// Leases a buffer, or returns `None` if they're all in use
+    pub fn try_get(&self) -> Option<FixedBuf> {
+        let index = self.shared.free.borrow_mut().pop()?;
+
+        Some(FixedBuf { shared: self.shared.clone(), index, len: 0 })
+    }
+
+    /// Leases a buffer, waiting for one to be returned if they're all in use
+    pub async fn get(&self) -> FixedBuf {
+        poll_fn(|cx| match self.try_get() {
+            Some(buf) => Poll::Ready(buf),
+            None => {
+                self.shared.waiters.borrow_mut().push(cx.waker().clone());
+                Poll::Pending
+            }
+        }).await
+    }
+}
+
+impl Drop for BufferPool {
+    fn drop(&mut self) {
+        self.shared.registered.set(false);
+        RUNTIME.with_borrow_mut(|rt| rt.plat.unregister_buffers(self.shared.memory));
+    }
+}
+
+/// A buffer leased from a [`BufferPool`], `File` and socket IO on it uses `ReadFixed`/`WriteFixed`
+/// 
+/// Like a `Vec` with a fixed capacity, it derefs to its initialized bytes. Reads write into it from its start,
+/// overwriting what it held.
+pub struct FixedBuf {
+    shared: Rc<Shared>,
+    index: u16,
+    len: usize
+}
+
+impl FixedBuf {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.buf_size
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Sets the number of initialized bytes, the buffer's memory is always initialized
+    /// 
+    /// Panics if `len` is larger than the capacity
+    pub fn set_len(&mut self, len: usize) {
+        assert!(len <= self.capacity(), "length is larger than the buffer's capacity");
+        self.len = len;
+    }
+
+    /// Appends bytes to the buffer
+    /// 
+    /// Panics if they don't fit in the remaining capacity
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        let start = self.len;
+        self.set_len(start + data.len());
+        self[start..].copy_from_slice(data);
+    }
+
+    fn ptr(&self) -> *mut u8 {
+        unsafe { self.shared.memory.add(self.index as usize * self.shared.buf_size) }
+    }
+}
+
+impl Deref for FixedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr(), self.len) }
+    }
+}
+
+impl DerefMut for FixedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr(), self.len) }
+    }
+}
+
+impl Drop for FixedBuf {
+    fn drop(&mut self) {
+        self.shared.free.borrow_mut().push(self.index);
+
+        // Every waiter is woken in case some of them no longer want a buffer
+        let waiters = std::mem::take(&mut *self.shared.waiters.borrow_mut());
+        waiters.into_iter().for_each(Waker::wake);
+    }
+}
+
+unsafe impl IoBuf for FixedBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        self.ptr()
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.shared.buf_size
+    }
+
+    fn fixed_index(&self) -> Option<u16> {
+        // The runtime may have been replaced by one which doesn't have the buffers registered
+        let registered = self.shared.registered.get() && RUNTIME.with_borrow(|rt| rt.plat.id) == self.shared.plat;
+        registered.then_some(self.index)
+    }
+}
+
+unsafe impl IoBufMut for FixedBuf {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr()
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        if self.len < pos {
+            self.len = pos;
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::io;
+use std::any::Any;
+use std::rc::Rc;
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::alloc::{self, Layout};
+use io_uring::types::BufRingEntry;
+
+use super::IoBuf;
+use crate::RUNTIME;
+use crate::platform::Platform;
+
+/// Most entries the kernel accepts in a buffer ring
+const MAX_ENTRIES: u16 = 1 << 15;
+
+/// The buffers and ring shared with the kernel, kept alive until the ring, all of its buffers and the operations
+/// selecting from it are dropped
+pub (crate) struct RingShared {
+    ring: *mut BufRingEntry,
+    ring_layout: Layout,
+    memory: *mut u8,
+    memory_layout: Layout,
+    entries: u16,
+    buf_size: usize,
+    bgid: u16,
+
+    // ID of the platform the ring is registered with
+    plat: u64,
+
+    // Our copy of the tail, the kernel only reads the ring's tail
+    tail: Cell<u16>,
+
+    // Cleared once the ring is unregistered, buffers then stop being returned to it
+    registered: Cell<bool>
+}
+
+impl RingShared {
+    /// Hands a buffer back to the kernel
+    pub (crate) fn recycle(&self, bid: u16) {
+        if !self.registered.get() {
+            return;
+        }
+
+        let tail = self.tail.get();
+
+        unsafe {
+            let entry = &mut *self.ring.add((tail & (self.entries - 1)) as usize);
+            entry.set_addr(self.buf_ptr(bid) as u64);
+            entry.set_len(self.buf_size as u32);
+            entry.set_bid(bid);
+
+            // Publishes the entry to the kernel
+            let shared_tail = AtomicU16::from_ptr(BufRingEntry::tail(self.ring) as *mut u16);
+            shared_tail.store(tail.wrapping_add(1), Ordering::Release);
+        }
+
+        self.tail.set(tail.wrapping_add(1));
+    }
+
+    pub (crate) fn bgid(&self) -> u16 {
+        self.bgid
+    }
+
+    pub (crate) fn buf_size(&self) -> usize {
+        self.buf_size
+    }
+
+    fn buf_ptr(&self, bid: u16) -> *mut u8 {
+        unsafe { self.memory.add(bid as usize * self.buf_size) }
+    }
+
+    /// Unregisters the ring, which is only done once nothing holds it anymore so that its group ID
+    /// isn't handed out to another ring while operations may still select buffers from it
+    fn unregister(&self, plat: &mut Platform) {
+        if self.registered.replace(false) && plat.id == self.plat {
+            plat.unregister_buf_ring(self.bgid);
+        }
+    }
+}
+
+impl Drop for RingShared {
+    fn drop(&mut self) {
+        if self.registered.get() {
+            RUNTIME.with_borrow_mut(|rt| self.unregister(&mut rt.plat));
+        }
+
+        unsafe {
+            alloc::dealloc(self.ring as *mut u8, self.ring_layout);
+            alloc::dealloc(self.memory, self.memory_layout);
+        }
+    }
+}
+
+/// Returns a buffer selected by an operation whose completion nobody is waiting for to its ring,
+/// `data` being the data of the operation
+/// 
+/// Operations selecting buffers from a ring have it as their data.
+pub (crate) fn recycle_buffer(data: &dyn Any, bid: u16) {
+    if let Some(Some(ring)) = data.downcast_ref::<Option<Rc<RingShared>>>() {
+        ring.recycle(bid);
+    }
+}
+
+/// Unregisters the ring of an operation whose last completion was discarded if nothing else holds it,
+/// since the operation's data is then dropped while the runtime is borrowed
+pub (crate) fn release_ring(plat: &mut Platform, data: &dyn Any) {
+    if let Some(Some(ring)) = data.downcast_ref::<Option<Rc<RingShared>>>() {
+        if Rc::strong_count(ring) == 1 {
+            ring.unregister(plat);
+        }
+    }
+}
+
+/// A ring of buffers provided to the kernel (`IORING_REGISTER_PBUF_RING`), which picks one of them
+/// whenever data arrives instead of each operation holding a buffer while it waits
+/// 
+/// Buffers picked by the kernel are handed out as [`ProvidedBuf`]s, which go back to the ring when dropped.
+/// Operations fail with `ENOBUFS` if the ring runs out of buffers.
+pub struct BufRing {
+    shared: Rc<RingShared>
+}
+
+impl BufRing {
+    /// Allocates `entries` buffers of `buf_size` bytes each and registers them with the runtime's ring,
+    /// `entries` must be a power of two no larger than 32768
+    /// 
+    /// Requires Linux 5.19.
+    pub fn new(entries: u16, buf_size: usize) -> io::Result<Self> {
+        if !entries.is_power_of_two() || entries > MAX_ENTRIES || buf_size == 0 || buf_size > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid number or size of buffers"));
+        }
+
+        let invalid_size = || io::Error::new(io::ErrorKind::InvalidInput, "buffers are too large");
+
+        // The kernel requires the ring to be page aligned
+        let ring_layout = Layout::array::<BufRingEntry>(entries as usize)
+            .and_then(|layout| layout.align_to(4096))
+            .map_err(|_| invalid_size())?;
+
+        let memory_layout = (entries as usize).checked_mul(buf_size)
+            .and_then(|size| Layout::from_size_align(size, 4096).ok())
+            .ok_or_else(invalid_size)?;
+
+        let ring = unsafe { alloc::alloc_zeroed(ring_layout) } as *mut BufRingEntry;
+
+        if ring.is_null() {
+            alloc::handle_alloc_error(ring_layout);
+        }
+
+        let memory = unsafe { alloc::alloc_zeroed(memory_layout) };
+
+        if memory.is_null() {
+            unsafe { alloc::dealloc(ring as *mut u8, ring_layout) };
+            alloc::handle_alloc_error(memory_layout);
+        }
+
+        let mut shared = RingShared {
+            ring,
+            ring_layout,
+            memory,
+            memory_layout,
+            entries,
+            buf_size,
+            bgid: 0,
+            plat: 0,
+            tail: Cell::new(0),
+            registered: Cell::new(false)
+        };
+
+        // The memory stays alive until the ring, its buffers and the operations using it are dropped,
+        // which unregisters it
+        let registered = RUNTIME.with_borrow_mut(|rt| unsafe {
+            rt.plat.register_buf_ring(ring as u64, entries).map(|bgid| (bgid, rt.plat.id))
+        });
+
+        (shared.bgid, shared.plat) = registered?;
+        shared.registered.set(true);
+
+        for bid in 0..entries {
+            shared.recycle(bid);
+        }
+
+        Ok(Self { shared: Rc::new(shared) })
+    }
+
+    /// Size of each buffer
+    pub fn buf_size(&self) -> usize {
+        self.shared.buf_size
+    }
+
+    pub (crate) fn shared(&self) -> &Rc<RingShared> {
+        &self.shared
+    }
+}
+
+/// A buffer of a [`BufRing`] picked by the kernel, which goes back to the ring when dropped
+/// 
+/// It derefs to the bytes the kernel wrote into it, and can be written out like any other buffer.
+pub struct ProvidedBuf {
+    ring: Rc<RingShared>,
+    bid: u16,
+    len: usize
+}
+
+impl ProvidedBuf {
+    /// Takes the buffer picked by a completion, `len` being the number of bytes written into it
+    pub (crate) fn new(ring: Rc<RingShared>, bid: u16, len: usize) -> Self {
+        Self { ring, bid, len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Deref for ProvidedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ring.buf_ptr(self.bid), self.len) }
+    }
+}
+
+impl DerefMut for ProvidedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ring.buf_ptr(self.bid), self.len) }
+    }
+}
+
+impl Drop for ProvidedBuf {
+    fn drop(&mut self) {
+        self.ring.recycle(self.bid);
+    }
+}
+
+unsafe impl IoBuf for ProvidedBuf {
+    fn stable_ptr(&self) -> *const u8 {
+        self.ring.buf_ptr(self.bid)
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.len
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.len
+    }
+}
@@ -0,0 +1,79 @@
+use std::ops::{Bound, RangeBounds};
+
+use super::{IoBuf, IoBufMut};
+
+/// A range of an owned buffer, which can be handed to the kernel in place of the whole buffer
+/// 
+/// Created by [`IoBuf::slice()`], the buffer is taken back with [`into_inner()`](Slice::into_inner).
+pub struct Slice<B> {
+    buf: B,
+    begin: usize,
+    end: usize
+}
+
+impl<B: IoBuf> Slice<B> {
+    pub(super) fn new(buf: B, range: impl RangeBounds<usize>) -> Self {
+        let begin = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0
+        };
+
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => buf.bytes_total()
+        };
+
+        assert!(begin <= end, "slice starts at {begin} but ends at {end}");
+        assert!(end <= buf.bytes_total(), "slice ends at {end} but the buffer's size is {}", buf.bytes_total());
+
+        Self { buf, begin, end }
+    }
+
+    /// Offset of the start of the slice in the buffer
+    pub fn begin(&self) -> usize {
+        self.begin
+    }
+
+    /// Offset of the end of the slice in the buffer
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get_ref(&self) -> &B {
+        &self.buf
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+unsafe impl<B: IoBuf> IoBuf for Slice<B> {
+    fn stable_ptr(&self) -> *const u8 {
+        unsafe { self.buf.stable_ptr().add(self.begin) }
+    }
+
+    fn bytes_init(&self) -> usize {
+        self.buf.bytes_init().clamp(self.begin, self.end) - self.begin
+    }
+
+    fn bytes_total(&self) -> usize {
+        self.end - self.begin
+    }
+
+    fn fixed_index(&self) -> Option<u16> {
+        self.buf.fixed_index()
+    }
+}
+
+unsafe impl<B: IoBufMut> IoBufMut for Slice<B> {
+    fn stable_mut_ptr(&mut self) -> *mut u8 {
+        unsafe { self.buf.stable_mut_ptr().add(self.begin) }
+    }
+
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.buf.set_init(self.begin + pos);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    RUNTIME,
+    RUNNING,
+    PanicPolicy,
+    error::UringError,
+    runtime::Runtime,
+    platform::RingConfig,
+};
+
+/// Configures and initializes the thread-local runtime
+/// 
+/// [`init()`](crate::init) is equivalent to `Builder::new().init()`
+pub struct Builder {
+    ring: RingConfig,
+    panic_policy: PanicPolicy
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            ring: RingConfig::default(),
+            panic_policy: PanicPolicy::default()
+        }
+    }
+
+    /// Sets the size of the submission queue, defaults to 128
+    pub fn entries(mut self, entries: u32) -> Self {
+        self.ring.entries = entries;
+        self
+    }
+
+    /// Sets the size of the completion queue (`IORING_SETUP_CQSIZE`), by
+    /// default the kernel makes it twice the size of the submission queue
+    pub fn cq_entries(mut self, cq_entries: u32) -> Self {
+        self.ring.cq_entries = Some(cq_entries);
+        self
+    }
+
+    /// Enables a kernel thread polling the submission queue (`IORING_SETUP_SQPOLL`),
+    /// which goes to sleep after being idle for `idle_millis`
+    pub fn sqpoll(mut self, idle_millis: u32) -> Self {
+        self.ring.sqpoll_idle = Some(idle_millis);
+        self
+    }
+
+    /// Pins the submission queue polling thread to a CPU, requires [`sqpoll()`](Self::sqpoll)
+    pub fn sqpoll_cpu(mut self, cpu: u32) -> Self {
+        self.ring.sqpoll_cpu = Some(cpu);
+        self
+    }
+
+    /// Sets `IORING_SETUP_COOP_TASKRUN`, completions no longer interrupt
+    /// the thread and are instead processed when it next waits for IO
+    pub fn coop_taskrun(mut self, coop_taskrun: bool) -> Self {
+        self.ring.coop_taskrun = coop_taskrun;
+        self
+    }
+
+    /// Sets `IORING_SETUP_SINGLE_ISSUER`, letting the kernel know only this thread submits IO
+    pub fn single_issuer(mut self, single_issuer: bool) -> Self {
+        self.ring.single_issuer = single_issuer;
+        self
+    }
+
+    /// Sets `IORING_SETUP_DEFER_TASKRUN`, completions are only processed when the thread
+    /// waits for IO, requires [`single_issuer()`](Self::single_issuer)
+    pub fn defer_taskrun(mut self, defer_taskrun: bool) -> Self {
+        self.ring.defer_taskrun = defer_taskrun;
+        self
+    }
+
+    /// Enables a table of `count` registered files, sockets and files opened by the runtime are then
+    /// placed directly into it, which saves looking up their fd on every operation
+    /// 
+    /// Files are still given a regular fd, which requires Linux 6.8.
+    /// Once the table is full, new files are only given a regular fd.
+    pub fn registered_files(mut self, count: u32) -> Self {
+        self.ring.registered_files = Some(count);
+        self
+    }
+
+    /// Sets what happens when a spawned task panics, see [`PanicPolicy`]
+    pub fn panic_policy(mut self, panic_policy: PanicPolicy) -> Self {
+        self.panic_policy = panic_policy;
+        self
+    }
+
+    /// Initializes the thread-local runtime with this configuration
+    /// 
+    /// This replaces the runtime if one was already initialized on this thread,
+    /// and must not be called from inside of [`run()`](crate::run)
+    pub fn init(self) -> Result<(), UringError> {
+        if RUNNING.get() {
+            panic!("init() called inside of a run() call!")
+        }
+
+        if self.ring.sqpoll_cpu.is_some() && self.ring.sqpoll_idle.is_none() {
+            return Err(UringError::InvalidConfig("sqpoll_cpu requires sqpoll"));
+        }
+
+        if self.ring.defer_taskrun && !self.ring.single_issuer {
+            return Err(UringError::InvalidConfig("defer_taskrun requires single_issuer"));
+        }
+
+        if self.ring.defer_taskrun && self.ring.sqpoll_idle.is_some() {
+            return Err(UringError::InvalidConfig("defer_taskrun can't be used with sqpoll"));
+        }
+
+        if self.ring.registered_files == Some(0) {
+            return Err(UringError::InvalidConfig("registered_files requires at least one file"));
+        }
+
+        RUNTIME.set(Runtime::new(self.ring, self.panic_policy)?);
+        Ok(())
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
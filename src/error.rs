@@ -1,4 +1,5 @@
 use std::io;
+use std::any::Any;
 
 use  thiserror::Error;
 
@@ -9,6 +10,10 @@ pub enum UringError {
     #[error("Failed to init rings: {0}")]
     FailedInit(io::Error),
 
+    #[cfg(target_os = "linux")]
+    #[error("Invalid runtime configuration: {0}")]
+    InvalidConfig(&'static str),
+
     #[cfg(target_os = "linux")]
     #[error("Feature [{0}] required but unsupported by current kernel")]
     UnsupportedFeature(&'static str),
@@ -28,4 +33,17 @@ pub enum UringError {
     #[error("Generic IO Error")]
     IOError(io::Error),
 
-}
\ No newline at end of file
+}
+
+/// Error returned when awaiting a [`JoinHandle`](crate::JoinHandle) whose task didn't finish
+#[derive(Error, Debug)]
+pub enum JoinError {
+
+    #[error("Task was cancelled")]
+    Cancelled,
+
+    /// The task panicked, this holds the panic's payload
+    #[error("Task panicked")]
+    Panic(Box<dyn Any + Send>),
+
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+use std::io::{Result, Error, ErrorKind};
+use std::path::Path;
+use std::os::fd::{OwnedFd, AsRawFd, RawFd};
+
+use super::{File, OpenOptions, Metadata, ReadDir};
+use crate::platform::{
+    file_open,
+    dir_open,
+    fs_open2,
+    fs_rename,
+    fs_unlink,
+    fs_mkdir,
+    fs_statx,
+    file_statx
+};
+
+/// A handle to an open directory, paths passed to its methods are resolved relative to it
+/// rather than to the current working directory
+/// 
+/// A [confined](Dir::confined) directory additionally rejects paths which would resolve outside of it,
+/// whether through `..`, absolute paths or symlinks, using `RESOLVE_BENEATH` (Linux 5.6).
+pub struct Dir {
+    fd: Arc<OwnedFd>,
+    confined: bool
+}
+
+impl Dir {
+    /// Opens the directory at a path relative to the current working directory, the returned directory isn't confined
+    pub async fn open_ambient<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let fd = dir_open(libc::AT_FDCWD, path.as_ref(), 0).await?;
+        Ok(Self { fd: Arc::new(fd), confined: false })
+    }
+
+    /// Sets whether paths are confined to this directory
+    pub fn confined(mut self, confined: bool) -> Self {
+        self.confined = confined;
+        self
+    }
+
+    pub fn is_confined(&self) -> bool {
+        self.confined
+    }
+
+    /// Opens a subdirectory, which is confined if this directory is
+    pub async fn open_dir<P: AsRef<Path>>(&self, path: P) -> Result<Dir> {
+        let fd = dir_open(self.raw_fd(), path.as_ref(), self.resolve()).await?;
+        Ok(Self { fd: Arc::new(fd), confined: self.confined })
+    }
+
+    pub async fn open<P: AsRef<Path>>(&self, path: P, opts: &OpenOptions) -> Result<File> {
+        let file = match self.confined {
+            true => file_open(self.raw_fd(), path.as_ref(), &opts.clone().resolve_beneath(true)).await?,
+            false => file_open(self.raw_fd(), path.as_ref(), opts).await?
+        };
+
+        Ok(File::from_handle(file))
+    }
+
+    /// Creates a directory, its parent must already exist
+    pub async fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let (parent, name) = self.resolve_parent(path.as_ref()).await?;
+        fs_mkdir(Some(parent), name, 0o777).await
+    }
+
+    /// Removes a file
+    pub async fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let (parent, name) = self.resolve_parent(path.as_ref()).await?;
+        fs_unlink(Some(parent), name, false).await
+    }
+
+    /// Removes an empty directory
+    pub async fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let (parent, name) = self.resolve_parent(path.as_ref()).await?;
+        fs_unlink(Some(parent), name, true).await
+    }
+
+    /// Renames a file or directory, replacing `to` if it already exists
+    pub async fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> Result<()> {
+        let (from_parent, from) = self.resolve_parent(from.as_ref()).await?;
+        let (to_parent, to) = self.resolve_parent(to.as_ref()).await?;
+
+        fs_rename(Some(from_parent), from, Some(to_parent), to).await
+    }
+
+    /// Gets the metadata of the file at a path, following symlinks
+    pub async fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<Metadata> {
+        if !self.confined {
+            return fs_statx(self.raw_fd(), path.as_ref(), true).await.map(Metadata::from_statx);
+        }
+
+        // statx can't restrict how the path is resolved, so open the file itself
+        let flags = libc::O_PATH | libc::O_CLOEXEC;
+        let file = fs_open2(self.raw_fd(), path.as_ref(), flags, 0, libc::RESOLVE_BENEATH).await?;
+
+        file_statx(&file).await.map(Metadata::from_statx)
+    }
+
+    /// Gets the metadata of the file at a path, without following symlinks
+    pub async fn symlink_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Metadata> {
+        let (parent, name) = self.resolve_parent(path.as_ref()).await?;
+        fs_statx(parent.as_raw_fd(), name, false).await.map(Metadata::from_statx)
+    }
+
+    /// Reads the entries of a subdirectory, use `"."` to read this directory
+    /// 
+    /// The paths of the returned entries are relative to this directory.
+    pub async fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<ReadDir> {
+        let path = path.as_ref();
+        let fd = dir_open(self.raw_fd(), path, self.resolve()).await?;
+
+        Ok(ReadDir::new(fd, path.to_owned()))
+    }
+
+    fn raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+
+    fn resolve(&self) -> u64 {
+        if self.confined { libc::RESOLVE_BENEATH } else { 0 }
+    }
+
+    /// Splits a path into its parent directory and final component, so that operations which can't restrict
+    /// how paths are resolved only see a single component, which they don't follow if it's a symlink
+    /// 
+    /// The parent is opened beneath this directory if it's confined.
+    async fn resolve_parent<'a>(&self, path: &'a Path) -> Result<(Arc<OwnedFd>, &'a Path)> {
+        if !self.confined {
+            return Ok((self.fd.clone(), path));
+        }
+
+        let name = path.file_name()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path doesn't end in a file name"))?;
+
+        let parent = match path.parent() {
+            Some(parent) if parent != Path::new("") => {
+                let flags = libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC;
+                let parent = fs_open2(self.raw_fd(), parent, flags, 0, libc::RESOLVE_BENEATH).await?;
+                Arc::new(OwnedFd::from(parent))
+            },
+            _ => self.fd.clone()
+        };
+
+        Ok((parent, Path::new(name)))
+    }
+}
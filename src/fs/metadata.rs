@@ -0,0 +1,186 @@
+use std::fmt;
+use std::io::{Result, Error, ErrorKind};
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, SystemTime};
+
+/// Metadata of a file, as returned by `statx`
+/// 
+/// This is returned by [`metadata()`](super::metadata), [`symlink_metadata()`](super::symlink_metadata)
+/// and [`File::metadata()`](super::File::metadata)
+#[derive(Clone)]
+pub struct Metadata(libc::statx);
+
+impl Metadata {
+    pub(crate) fn from_statx(stat: libc::statx) -> Self {
+        Self(stat)
+    }
+
+    pub fn file_type(&self) -> FileType {
+        FileType::from_mode(self.0.stx_mode as u32)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+
+    /// Size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.0.stx_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_mode(self.mode())
+    }
+
+    /// The file's mode, containing both its type and permissions
+    pub fn mode(&self) -> u32 {
+        self.0.stx_mode as u32
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.0.stx_uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.0.stx_gid
+    }
+
+    pub fn ino(&self) -> u64 {
+        self.0.stx_ino
+    }
+
+    pub fn nlink(&self) -> u64 {
+        self.0.stx_nlink as u64
+    }
+
+    /// ID of the device containing the file
+    pub fn dev(&self) -> u64 {
+        libc::makedev(self.0.stx_dev_major, self.0.stx_dev_minor)
+    }
+
+    /// ID of the device this file represents, if it is a device file
+    pub fn rdev(&self) -> u64 {
+        libc::makedev(self.0.stx_rdev_major, self.0.stx_rdev_minor)
+    }
+
+    /// Preferred block size for IO on the file
+    pub fn blksize(&self) -> u64 {
+        self.0.stx_blksize as u64
+    }
+
+    /// Number of 512 byte blocks allocated to the file
+    pub fn blocks(&self) -> u64 {
+        self.0.stx_blocks
+    }
+
+    /// The `STATX_ATTR_*` attributes of the file
+    pub fn attributes(&self) -> u64 {
+        self.0.stx_attributes
+    }
+
+    /// ID of the mount containing the file, `None` if not reported by the filesystem or kernel
+    pub fn mount_id(&self) -> Option<u64> {
+        self.has(libc::STATX_MNT_ID).then_some(self.0.stx_mnt_id)
+    }
+
+    pub fn accessed(&self) -> Result<SystemTime> {
+        self.time(libc::STATX_ATIME, &self.0.stx_atime)
+    }
+
+    pub fn modified(&self) -> Result<SystemTime> {
+        self.time(libc::STATX_MTIME, &self.0.stx_mtime)
+    }
+
+    /// Time the file's metadata was last changed
+    pub fn changed(&self) -> Result<SystemTime> {
+        self.time(libc::STATX_CTIME, &self.0.stx_ctime)
+    }
+
+    /// Time the file was created, not every filesystem records this
+    pub fn created(&self) -> Result<SystemTime> {
+        self.time(libc::STATX_BTIME, &self.0.stx_btime)
+    }
+
+    fn has(&self, field: u32) -> bool {
+        self.0.stx_mask & field != 0
+    }
+
+    fn time(&self, field: u32, time: &libc::statx_timestamp) -> Result<SystemTime> {
+        if !self.has(field) {
+            return Err(Error::new(ErrorKind::Unsupported, "field not available on this platform or filesystem"));
+        }
+
+        let since_epoch = Duration::new(time.tv_sec.unsigned_abs(), time.tv_nsec);
+
+        Ok(if time.tv_sec >= 0 {
+            SystemTime::UNIX_EPOCH + since_epoch
+        }
+        else {
+            SystemTime::UNIX_EPOCH - since_epoch
+        })
+    }
+}
+
+impl fmt::Debug for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metadata")
+            .field("file_type", &self.file_type())
+            .field("len", &self.len())
+            .field("permissions", &self.permissions())
+            .field("modified", &self.modified())
+            .field("accessed", &self.accessed())
+            .field("created", &self.created())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Type of a file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FileType(u32);
+
+impl FileType {
+    pub(crate) fn from_mode(mode: u32) -> Self {
+        Self(mode & libc::S_IFMT)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.0 == libc::S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.0 == libc::S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.0 == libc::S_IFLNK
+    }
+
+    pub fn is_block_device(&self) -> bool {
+        self.0 == libc::S_IFBLK
+    }
+
+    pub fn is_char_device(&self) -> bool {
+        self.0 == libc::S_IFCHR
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.0 == libc::S_IFIFO
+    }
+
+    pub fn is_socket(&self) -> bool {
+        self.0 == libc::S_IFSOCK
+    }
+}
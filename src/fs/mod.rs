@@ -0,0 +1,547 @@
+mod metadata;
+mod read_dir;
+mod dir;
+
+pub use metadata::{Metadata, FileType};
+pub use read_dir::{ReadDir, DirEntry, read_dir};
+pub use dir::Dir;
+
+use std::path::Path;
+use std::io::{Result, Error, ErrorKind, Seek, SeekFrom};
+
+use crate::buf::{IoBuf, IoBufMut};
+use crate::platform::{
+    Handle,
+    file_open,
+    file_write,
+    file_read,
+    file_read_vectored,
+    file_write_vectored,
+    file_read_append,
+    file_write_from,
+    file_copy,
+    file_seekable,
+    file_appends,
+    file_sync,
+    file_sync_range,
+    file_fallocate,
+    file_truncate,
+    fs_rename,
+    fs_unlink,
+    fs_mkdir,
+    fs_hard_link,
+    fs_symlink,
+    fs_statx,
+    file_statx,
+    CURRENT_POS,
+};
+
+#[derive(Clone)]
+pub struct OpenOptions {
+    pub(crate) read: bool,
+    pub(crate) write: bool,
+    pub(crate) append: bool,
+    pub(crate) truncate: bool,
+    pub(crate) create: bool,
+    pub(crate) create_new: bool,
+    pub(crate) tmpfile: bool,
+    pub(crate) direct: bool,
+    pub(crate) cloexec: bool,
+    pub(crate) mode: u32,
+    pub(crate) custom_flags: i32,
+    pub(crate) resolve: u64
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            tmpfile: false,
+            direct: false,
+            cloexec: true,
+            mode: 0o666,
+            custom_flags: 0,
+            resolve: 0
+        }
+    }
+
+    pub fn read(mut self, read: bool) -> Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(mut self, write: bool) -> Self {
+        self.write = write;
+        self
+    }
+
+    pub fn append(self, append: bool) -> Self {
+        let mut this = self.write(true);
+        this.append = append;
+        this
+    }
+
+    pub fn truncate(mut self, truncate: bool) -> Self {
+        self.truncate = truncate;
+        self
+    }
+
+    pub fn create(mut self, create: bool) -> Self {
+        self.create = create;
+        self
+    }
+
+    pub fn create_new(mut self, create_new: bool) -> Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Creates an unnamed file (`O_TMPFILE`) inside of the directory at the opened path,
+    /// the file is deleted once it's closed
+    /// 
+    /// This implies write access. Opening fails with `InvalidInput` if `create` or `create_new` is also set.
+    pub fn tmpfile(self, tmpfile: bool) -> Self {
+        let mut this = self.write(true);
+        this.tmpfile = tmpfile;
+        this
+    }
+
+    /// Bypasses the page cache (`O_DIRECT`), buffers and offsets then usually have to be
+    /// aligned to the filesystem's block size
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Closes the file when executing another program (`O_CLOEXEC`), enabled by default
+    pub fn cloexec(mut self, cloexec: bool) -> Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Permissions of newly created files before the process' umask is applied, defaults to `0o666`
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Additional `O_*` flags passed to `open`, the access mode bits are ignored
+    pub fn custom_flags(mut self, flags: i32) -> Self {
+        self.custom_flags = flags;
+        self
+    }
+
+    /// Fails to open paths which resolve outside of the starting directory (`RESOLVE_BENEATH`),
+    /// either through `..`, absolute paths or symlinks
+    /// 
+    /// Setting any resolve flag opens the file with `openat2`, which requires Linux 5.6.
+    pub fn resolve_beneath(mut self, beneath: bool) -> Self {
+        self.set_resolve(libc::RESOLVE_BENEATH, beneath);
+        self
+    }
+
+    /// Fails to open paths containing symlinks in any of their components (`RESOLVE_NO_SYMLINKS`)
+    pub fn resolve_no_symlinks(mut self, no_symlinks: bool) -> Self {
+        self.set_resolve(libc::RESOLVE_NO_SYMLINKS, no_symlinks);
+        self
+    }
+
+    fn set_resolve(&mut self, flag: u64, set: bool) {
+        if set {
+            self.resolve |= flag;
+        }
+        else {
+            self.resolve &= !flag;
+        }
+    }
+}
+
+/// A file, reads and writes go through a cursor which is advanced by the number of bytes read or written,
+/// or can be done at explicit offsets with [`read_at()`](File::read_at) and [`write_at()`](File::write_at)
+/// 
+/// The cursor is only tracked for seekable files which weren't opened in append mode, reads and writes on
+/// other files (pipes, character devices...) use the kernel's file position instead.
+pub struct File {
+    file: Handle<std::fs::File>,
+
+    // `None` if the file isn't seekable or appends
+    pos: Option<u64>
+}
+
+impl File {
+    pub async fn open<T: AsRef<Path>>(path: T, opts: &OpenOptions) -> Result<Self> {
+        file_open(libc::AT_FDCWD, path.as_ref(), opts)
+        .await
+        .map(Self::from_handle)
+    }
+
+    fn from_handle(file: Handle<std::fs::File>) -> Self {
+        // Writes to files in append mode go to the end of the file wherever the cursor is
+        let pos = (file_seekable(&file) && !file_appends(&file)).then_some(0);
+
+        Self { file, pos }
+    }
+
+    /// Reads into the buffer at the cursor, advancing it by the number of bytes read
+    /// 
+    /// Returns the number of bytes read along with the buffer
+    pub async fn read<B: IoBufMut>(&mut self, buf: B) -> (Result<usize>, B) {
+        let (res, buf) = file_read(&self.file, buf, self.cursor()).await;
+        self.advance(&res);
+        (res, buf)
+    }
+
+    /// Writes the buffer's initialized bytes at the cursor, advancing it by the number of bytes written
+    /// 
+    /// Returns the number of bytes written along with the buffer
+    pub async fn write<B: IoBuf>(&mut self, buf: B) -> (Result<usize>, B) {
+        let (res, buf) = file_write(&self.file, buf, self.cursor()).await;
+        self.advance(&res);
+        (res, buf)
+    }
+
+    /// Reads into the buffer at the given offset, without using or moving the cursor
+    /// 
+    /// Returns the number of bytes read along with the buffer
+    pub async fn read_at<B: IoBufMut>(&self, buf: B, offset: u64) -> (Result<usize>, B) {
+        file_read(&self.file, buf, offset).await
+    }
+
+    /// Writes the buffer's initialized bytes at the given offset, without using or moving the cursor
+    /// 
+    /// Returns the number of bytes written along with the buffer
+    pub async fn write_at<B: IoBuf>(&self, buf: B, offset: u64) -> (Result<usize>, B) {
+        file_write(&self.file, buf, offset).await
+    }
+
+    /// Reads into the buffers in order at the cursor, advancing it by the number of bytes read
+    /// 
+    /// Returns the number of bytes read along with the buffers
+    pub async fn read_vectored<B: IoBufMut>(&mut self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        let (res, bufs) = file_read_vectored(&self.file, bufs, self.cursor()).await;
+        self.advance(&res);
+        (res, bufs)
+    }
+
+    /// Writes the initialized bytes of the buffers in order at the cursor, advancing it by the number of bytes written
+    /// 
+    /// Returns the number of bytes written along with the buffers
+    pub async fn write_vectored<B: IoBuf>(&mut self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        let (res, bufs) = file_write_vectored(&self.file, bufs, self.cursor()).await;
+        self.advance(&res);
+        (res, bufs)
+    }
+
+    /// Reads into the buffers in order at the given offset, without using or moving the cursor
+    /// 
+    /// Returns the number of bytes read along with the buffers
+    pub async fn read_vectored_at<B: IoBufMut>(&self, bufs: Vec<B>, offset: u64) -> (Result<usize>, Vec<B>) {
+        file_read_vectored(&self.file, bufs, offset).await
+    }
+
+    /// Writes the initialized bytes of the buffers in order at the given offset, without using or moving the cursor
+    /// 
+    /// Returns the number of bytes written along with the buffers
+    pub async fn write_vectored_at<B: IoBuf>(&self, bufs: Vec<B>, offset: u64) -> (Result<usize>, Vec<B>) {
+        file_write_vectored(&self.file, bufs, offset).await
+    }
+
+    /// Reads at the cursor into the vector's spare capacity, appending the bytes read to it
+    async fn read_append(&mut self, buf: Vec<u8>) -> (Result<usize>, Vec<u8>) {
+        let (res, buf) = file_read_append(&self.file, buf, self.cursor()).await;
+        self.advance(&res);
+        (res, buf)
+    }
+
+    /// Writes the buffer's initialized bytes starting from `start` at the cursor
+    async fn write_from<B: IoBuf>(&mut self, buf: B, start: usize) -> (Result<usize>, B) {
+        let (res, buf) = file_write_from(&self.file, buf, start, self.cursor()).await;
+        self.advance(&res);
+        (res, buf)
+    }
+
+    /// Reads at the kernel's file position instead of the cursor, for files without one which
+    /// are shared between operations
+    #[cfg(any(feature = "futures-io", feature = "tokio-io"))]
+    pub (crate) async fn read_shared<B: IoBufMut>(&self, buf: B) -> (Result<usize>, B) {
+        file_read(&self.file, buf, CURRENT_POS).await
+    }
+
+    /// Writes at the kernel's file position instead of the cursor, see [`read_shared()`](Self::read_shared)
+    #[cfg(any(feature = "futures-io", feature = "tokio-io"))]
+    pub (crate) async fn write_shared<B: IoBuf>(&self, buf: B) -> (Result<usize>, B) {
+        file_write(&self.file, buf, CURRENT_POS).await
+    }
+
+    /// The cursor, `None` if the file isn't seekable or appends
+    #[cfg(any(feature = "futures-io", feature = "tokio-io"))]
+    pub (crate) fn position(&self) -> Option<u64> {
+        self.pos
+    }
+
+    /// Moves the cursor, if the file has one
+    #[cfg(any(feature = "futures-io", feature = "tokio-io"))]
+    pub (crate) fn set_position(&mut self, pos: u64) {
+        if let Some(cursor) = &mut self.pos {
+            *cursor = pos;
+        }
+    }
+
+    fn cursor(&self) -> u64 {
+        self.pos.unwrap_or(CURRENT_POS)
+    }
+
+    fn advance(&mut self, res: &Result<usize>) {
+        if let (Some(pos), Ok(n)) = (&mut self.pos, res) {
+            *pos += *n as u64;
+        }
+    }
+
+    pub async fn metadata(&self) -> Result<Metadata> {
+        file_statx(&self.file).await.map(Metadata::from_statx)
+    }
+
+    /// Flushes the file's data and metadata to disk
+    pub async fn sync_all(&self) -> Result<()> {
+        file_sync(&self.file, false).await
+    }
+
+    /// Flushes the file's data to disk, along with only the metadata needed to read it back
+    /// (e.g. the size, but not the modification time)
+    pub async fn sync_data(&self) -> Result<()> {
+        file_sync(&self.file, true).await
+    }
+
+    /// Writes out the dirty pages in the given range of the file and waits for them to be written
+    /// 
+    /// This uses `sync_file_range`, which neither flushes the file's metadata nor the disk's write cache,
+    /// so unlike [`sync_data()`](File::sync_data) it gives no durability guarantees on its own.
+    /// A `len` of 0 syncs everything from `offset` to the end of the file.
+    pub async fn sync_range(&self, offset: u64, len: u64) -> Result<()> {
+        let flags = libc::SYNC_FILE_RANGE_WAIT_BEFORE | libc::SYNC_FILE_RANGE_WRITE | libc::SYNC_FILE_RANGE_WAIT_AFTER;
+
+        // The ring only takes 32 bit lengths, so larger ranges are split up. A length of 0 is passed
+        // through as is.
+        let mut offset = offset;
+        let mut len = len;
+
+        loop {
+            let chunk = len.min(u32::MAX as u64);
+
+            file_sync_range(&self.file, offset, chunk as u32, flags).await?;
+
+            offset += chunk;
+            len -= chunk;
+
+            if len == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Allocates disk space for the given range, growing the file if the range goes past its end
+    /// 
+    /// Writes into the range are then guaranteed not to fail for lack of disk space
+    pub async fn allocate(&self, offset: u64, len: u64) -> Result<()> {
+        file_fallocate(&self.file, offset, len, 0).await
+    }
+
+    /// Deallocates the given range, which then reads back as zeroes, the file's size is unchanged
+    pub async fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        file_fallocate(&self.file, offset, len, libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE).await
+    }
+
+    /// Truncates or extends the file to the given size, extending it fills it with zeroes
+    /// 
+    /// The cursor is left unchanged, even if it ends up past the end of the file
+    pub async fn set_len(&self, size: u64) -> Result<()> {
+        file_truncate(&self.file, size).await
+    }
+
+    /// Moves the cursor, returning its new position from the start of the file
+    /// 
+    /// Files which don't track a cursor move the kernel's file position instead, this fails for files which
+    /// aren't seekable
+    pub async fn seek(&mut self, seek: SeekFrom) -> Result<u64> {
+        let Some(pos) = self.pos else {
+            // lseek doesn't block, so there's no need to go through the ring
+            return (&*self.file).seek(seek);
+        };
+
+        let new_pos = match seek {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.metadata().await?.len().checked_add_signed(offset)
+        };
+
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = Some(new_pos);
+                Ok(new_pos)
+            },
+            None => Err(Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))
+        }
+    }
+}
+
+/// Renames a file or directory, replacing `to` if it already exists
+pub async fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<()> {
+    fs_rename(None, from.as_ref(), None, to.as_ref()).await
+}
+
+/// Removes a file
+pub async fn remove_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    fs_unlink(None, path.as_ref(), false).await
+}
+
+/// Removes an empty directory
+pub async fn remove_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    fs_unlink(None, path.as_ref(), true).await
+}
+
+/// Creates a directory, its parent must already exist
+pub async fn create_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    fs_mkdir(None, path.as_ref(), 0o777).await
+}
+
+/// Creates a directory along with all of its missing parents
+/// 
+/// Succeeds if the directory already exists
+pub async fn create_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    let mut missing = Vec::new();
+    let mut current = path;
+
+    // Walk up the path until we manage to create a directory or reach one which already exists
+    loop {
+        match create_dir(current).await {
+            Ok(()) => break,
+
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                missing.push(current);
+
+                match current.parent() {
+                    Some(parent) if parent != Path::new("") => current = parent,
+                    _ => return Err(err)
+                }
+            },
+
+            Err(_) if is_dir(current).await => break,
+            Err(err) => return Err(err)
+        }
+    }
+
+    // Then create the missing directories on the way back down, another task
+    // may be creating them at the same time so existing ones are fine
+    for dir in missing.into_iter().rev() {
+        match create_dir(dir).await {
+            Ok(()) => (),
+            Err(_) if is_dir(dir).await => (),
+            Err(err) => return Err(err)
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a hard link called `link` to the `original` file
+pub async fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q) -> Result<()> {
+    fs_hard_link(original.as_ref(), link.as_ref()).await
+}
+
+/// Creates a symbolic link called `link` pointing to `original`
+pub async fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q) -> Result<()> {
+    fs_symlink(original.as_ref(), link.as_ref()).await
+}
+
+/// Gets the metadata of the file at a path, following symlinks
+pub async fn metadata<P: AsRef<Path>>(path: P) -> Result<Metadata> {
+    fs_statx(libc::AT_FDCWD, path.as_ref(), true).await.map(Metadata::from_statx)
+}
+
+/// Gets the metadata of the file at a path, without following symlinks
+pub async fn symlink_metadata<P: AsRef<Path>>(path: P) -> Result<Metadata> {
+    fs_statx(libc::AT_FDCWD, path.as_ref(), false).await.map(Metadata::from_statx)
+}
+
+/// Reads the whole contents of a file
+pub async fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let mut file = File::open(path, &OpenOptions::new().read(true)).await?;
+
+    // One extra byte so that reading the end of the file doesn't need to grow the buffer,
+    // the size is only a hint as some files (e.g. in /proc) report a size of 0
+    let size = file.metadata().await.map_or(0, |meta| meta.len() as usize);
+    let mut buf = Vec::with_capacity(size + 1);
+
+    loop {
+        if buf.len() == buf.capacity() {
+            buf.reserve(READ_CHUNK);
+        }
+
+        let (res, returned) = file.read_append(buf).await;
+        buf = returned;
+
+        match res {
+            Ok(0) => return Ok(buf),
+            Ok(_) => (),
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err)
+        }
+    }
+}
+
+/// Size the buffer of [`read()`] grows by once a file turns out to be larger than reported
+const READ_CHUNK: usize = 8 * 1024;
+
+/// Reads the whole contents of a file into a string, failing if it isn't valid UTF-8
+pub async fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
+    String::from_utf8(read(path).await?)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "file did not contain valid UTF-8"))
+}
+
+/// Writes the buffer's initialized bytes as the whole contents of a file,
+/// creating it if it doesn't exist and truncating it if it does
+pub async fn write<P: AsRef<Path>, B: IoBuf>(path: P, contents: B) -> Result<()> {
+    let opts = OpenOptions::new().write(true).create(true).truncate(true);
+    let mut file = File::open(path, &opts).await?;
+
+    let mut buf = contents;
+    let mut written = 0;
+
+    while written < buf.bytes_init() {
+        let (res, returned) = file.write_from(buf, written).await;
+        buf = returned;
+
+        match res {
+            Ok(0) => return Err(Error::from(ErrorKind::WriteZero)),
+            Ok(n) => written += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err)
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies the contents of a file to another, creating it with the same permissions if it doesn't exist
+/// and truncating it if it does, returns the number of bytes copied
+/// 
+/// The data is spliced between the files through a pipe, so it never gets copied to userspace.
+pub async fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<u64> {
+    let from = File::open(from, &OpenOptions::new().read(true)).await?;
+    let mode = from.metadata().await?.mode() & 0o7777;
+
+    let opts = OpenOptions::new().write(true).create(true).truncate(true).mode(mode);
+    let to = File::open(to, &opts).await?;
+
+    file_copy(&from.file, from.pos, &to.file, to.pos).await
+}
+
+async fn is_dir(path: &Path) -> bool {
+    metadata(path).await.is_ok_and(|meta| meta.is_dir())
+}
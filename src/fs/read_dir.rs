@@ -0,0 +1,164 @@
+use std::sync::Arc;
+use std::ffi::OsString;
+use std::collections::VecDeque;
+use std::io::{Result, Error};
+use std::thread;
+use std::path::{Path, PathBuf};
+use std::os::fd::{OwnedFd, AsRawFd};
+
+use super::{Metadata, FileType};
+use crate::platform::{dir_open, dir_read, parse_dirents, fs_statx, BlockingFut};
+
+/// Size of the buffer entries are read into, each read fills it with as many entries as fit
+const BUF_SIZE: usize = 32 * 1024;
+
+/// Reads the entries of a directory
+/// 
+/// The returned [`ReadDir`] yields the directory's entries, excluding `.` and `..`
+pub async fn read_dir<P: AsRef<Path>>(path: P) -> Result<ReadDir> {
+    let path = path.as_ref();
+    let fd = dir_open(libc::AT_FDCWD, path, 0).await?;
+
+    Ok(ReadDir::new(fd, path.to_owned()))
+}
+
+/// Stream of the entries of a directory, returned by [`read_dir()`]
+pub struct ReadDir {
+    dir: Arc<OwnedFd>,
+    path: Arc<PathBuf>,
+    entries: VecDeque<DirEntry>,
+
+    // `None` while a read is in progress
+    buf: Option<Vec<u8>>,
+
+    // Read in progress, kept if `next()` is cancelled so that the entries it reads aren't lost
+    read: Option<BlockingFut<(Result<usize>, Vec<u8>)>>,
+    done: bool
+}
+
+impl ReadDir {
+    pub(super) fn new(dir: OwnedFd, path: PathBuf) -> Self {
+        Self {
+            dir: Arc::new(dir),
+            path: Arc::new(path),
+            entries: VecDeque::new(),
+            buf: Some(Vec::with_capacity(BUF_SIZE)),
+            read: None,
+            done: false
+        }
+    }
+
+    /// Returns the next entry, or `None` once all entries have been returned
+    /// 
+    /// Entries are read in batches, so most calls return immediately.
+    pub async fn next(&mut self) -> Option<Result<DirEntry>> {
+        while self.entries.is_empty() && !self.done {
+            if self.read.is_none() {
+                // The buffer is only missing if the read holding it panicked
+                let Some(buf) = self.buf.take() else {
+                    self.done = true;
+                    return Some(Err(Error::other("an earlier read of the directory panicked")));
+                };
+
+                self.read = Some(dir_read(&self.dir, buf));
+            }
+
+            let (res, buf) = {
+                let read = ResetOnPanic(&mut self.read);
+                read.0.as_mut().unwrap().await
+            };
+
+            self.read = None;
+
+            match res {
+                Ok(0) => self.done = true,
+                Ok(_) => self.parse_entries(&buf),
+                Err(err) => {
+                    self.buf = Some(buf);
+                    return Some(Err(err));
+                }
+            }
+
+            self.buf = Some(buf);
+        }
+
+        self.entries.pop_front().map(Ok)
+    }
+
+    fn parse_entries(&mut self, buf: &[u8]) {
+        let entries = parse_dirents(buf)
+            .filter(|entry| entry.name != "." && entry.name != "..")
+            .map(|entry| DirEntry {
+                dir: self.dir.clone(),
+                dir_path: self.path.clone(),
+                name: entry.name.to_owned(),
+                ino: entry.ino,
+                d_type: entry.d_type
+            });
+
+        self.entries.extend(entries);
+    }
+}
+
+/// Drops the read it guards if polling it panics, since it's done and must not be polled again
+struct ResetOnPanic<'a>(&'a mut Option<BlockingFut<(Result<usize>, Vec<u8>)>>);
+
+impl Drop for ResetOnPanic<'_> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            *self.0 = None;
+        }
+    }
+}
+
+/// An entry of a directory, returned by [`ReadDir`]
+pub struct DirEntry {
+    dir: Arc<OwnedFd>,
+    dir_path: Arc<PathBuf>,
+    name: OsString,
+    ino: u64,
+    d_type: u8
+}
+
+impl DirEntry {
+    /// Full path of the entry, this is the directory's path passed to [`read_dir()`] joined with the entry's name
+    pub fn path(&self) -> PathBuf {
+        self.dir_path.join(&self.name)
+    }
+
+    pub fn file_name(&self) -> OsString {
+        self.name.clone()
+    }
+
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Type of the entry, this doesn't follow symlinks
+    /// 
+    /// This is usually known from reading the directory, but some filesystems don't report it,
+    /// in which case it is fetched along with the entry's metadata.
+    pub async fn file_type(&self) -> Result<FileType> {
+        let mode = match self.d_type {
+            libc::DT_DIR => libc::S_IFDIR,
+            libc::DT_REG => libc::S_IFREG,
+            libc::DT_LNK => libc::S_IFLNK,
+            libc::DT_BLK => libc::S_IFBLK,
+            libc::DT_CHR => libc::S_IFCHR,
+            libc::DT_FIFO => libc::S_IFIFO,
+            libc::DT_SOCK => libc::S_IFSOCK,
+            _ => return self.metadata().await.map(|meta| meta.file_type())
+        };
+
+        Ok(FileType::from_mode(mode))
+    }
+
+    /// Metadata of the entry, this doesn't follow symlinks
+    /// 
+    /// It is fetched relative to the directory, so it isn't affected by the directory being moved.
+    pub async fn metadata(&self) -> Result<Metadata> {
+        fs_statx(self.dir.as_raw_fd(), Path::new(&self.name), false)
+            .await
+            .map(Metadata::from_statx)
+    }
+}
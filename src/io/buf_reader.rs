@@ -0,0 +1,184 @@
+use std::io::{Result, Error, ErrorKind};
+use std::{mem, ptr};
+
+use crate::buf::IoBufMut;
+use super::{AsyncRead, DEFAULT_BUF_SIZE};
+
+/// Reader reading ahead into a buffer, so that small reads don't each go to the kernel
+/// 
+/// The buffer is lent to the kernel while reading into it, if such a read is cancelled (its future
+/// dropped) the data it reads is lost.
+pub struct BufReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    capacity: usize
+}
+
+impl<R: AsyncRead> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    /// Creates a reader reading up to `capacity` bytes at a time
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        Self { inner, buf: Vec::with_capacity(capacity), pos: 0, capacity }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Reading directly from the inner reader skips the buffered data
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Takes back the inner reader, discarding the buffered data
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Bytes read ahead and not yet consumed
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// Returns the buffered data, reading more if there is none, an empty slice meaning that the end of the reader
+    /// was reached
+    /// 
+    /// The data has to be marked as read with [`consume()`](BufReader::consume).
+    pub async fn fill_buf(&mut self) -> Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            let mut buf = mem::take(&mut self.buf);
+            buf.clear();
+            buf.reserve(self.capacity);
+            self.pos = 0;
+
+            let (res, buf) = self.inner.read(buf).await;
+            self.buf = buf;
+
+            if let Err(err) = res {
+                self.buf.clear();
+                return Err(err);
+            }
+        }
+
+        Ok(self.buffer())
+    }
+
+    /// Marks `amt` bytes of the buffered data as read
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+
+    /// Reads until `byte` or the end of the reader, appending the bytes read to `buf` including `byte`
+    /// 
+    /// Returns the number of bytes read, 0 meaning that the end of the reader was reached.
+    pub async fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut read = 0;
+
+        loop {
+            let (done, used) = {
+                let available = self.fill_buf().await?;
+
+                match available.iter().position(|&b| b == byte) {
+                    Some(i) => {
+                        buf.extend_from_slice(&available[..=i]);
+                        (true, i + 1)
+                    },
+
+                    None => {
+                        buf.extend_from_slice(available);
+                        (available.is_empty(), available.len())
+                    }
+                }
+            };
+
+            self.consume(used);
+            read += used;
+
+            if done {
+                return Ok(read);
+            }
+        }
+    }
+
+    /// Reads until a newline or the end of the reader, appending the line read to `buf` including the newline
+    /// 
+    /// Returns the number of bytes read, 0 meaning that the end of the reader was reached.
+    /// Fails with `InvalidData` if the line isn't valid UTF-8, `buf` is then left untouched.
+    pub async fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let mut bytes = Vec::new();
+        let read = self.read_until(b'\n', &mut bytes).await?;
+
+        let line = String::from_utf8(bytes)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"))?;
+
+        buf.push_str(&line);
+        Ok(read)
+    }
+
+    /// Returns a stream of the lines of the reader, without their trailing `\n` or `\r\n`
+    pub fn lines(self) -> Lines<R> {
+        Lines { reader: self }
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for BufReader<R> {
+    async fn read<B: IoBufMut>(&mut self, mut buf: B) -> (Result<usize>, B) {
+        // Reads at least as large as the buffer gain nothing from going through it
+        if self.pos == self.buf.len() && buf.bytes_total() >= self.capacity {
+            return self.inner.read(buf).await;
+        }
+
+        let available = match self.fill_buf().await {
+            Ok(available) => available,
+            Err(err) => return (Err(err), buf)
+        };
+
+        let n = available.len().min(buf.bytes_total());
+
+        unsafe {
+            ptr::copy_nonoverlapping(available.as_ptr(), buf.stable_mut_ptr(), n);
+            buf.set_init(n);
+        }
+
+        self.consume(n);
+        (Ok(n), buf)
+    }
+}
+
+/// Stream of the lines of a [`BufReader`], see [`BufReader::lines()`]
+pub struct Lines<R> {
+    reader: BufReader<R>
+}
+
+impl<R: AsyncRead> Lines<R> {
+    /// Reads the next line, returns `None` once the end of the reader was reached
+    pub async fn next(&mut self) -> Option<Result<String>> {
+        let mut line = String::new();
+
+        match self.reader.read_line(&mut line).await {
+            Ok(0) => None,
+
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+
+                Some(Ok(line))
+            },
+
+            Err(err) => Some(Err(err))
+        }
+    }
+
+    pub fn into_inner(self) -> BufReader<R> {
+        self.reader
+    }
+}
@@ -0,0 +1,101 @@
+use std::io::{Result, Error, ErrorKind};
+use std::mem;
+
+use crate::buf::IoBuf;
+use super::{AsyncWrite, DEFAULT_BUF_SIZE};
+
+/// Writer gathering small writes into a buffer, which is written out in a single operation once it's full
+/// or flushed
+/// 
+/// Buffered data which wasn't flushed is discarded when the writer is dropped, and if a write of the buffer
+/// is cancelled (its future dropped) the data it was writing is lost.
+pub struct BufWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+    capacity: usize
+}
+
+impl<W: AsyncWrite> BufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, inner)
+    }
+
+    /// Creates a writer buffering up to `capacity` bytes
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        Self { inner, buf: Vec::with_capacity(capacity), capacity }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Writing directly to the inner writer skips ahead of the buffered data
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Takes back the inner writer, discarding the buffered data, use [`flush()`](AsyncWrite::flush) before
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Bytes written and not yet flushed
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Writes out the buffer, keeping the bytes which weren't written if it fails
+    async fn flush_buf(&mut self) -> Result<()> {
+        let mut buf = mem::take(&mut self.buf).slice(..);
+        let len = buf.get_ref().len();
+        let mut written = 0;
+
+        let res = loop {
+            if written == len {
+                break Ok(());
+            }
+
+            let (res, slice) = self.inner.write(buf.into_inner().slice(written..)).await;
+            buf = slice;
+
+            match res {
+                Ok(0) => break Err(Error::new(ErrorKind::WriteZero, "failed to write the buffered data")),
+                Ok(n) => written += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {},
+                Err(err) => break Err(err)
+            }
+        };
+
+        self.buf = buf.into_inner();
+        self.buf.drain(..written);
+
+        res
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for BufWriter<W> {
+    async fn write<B: IoBuf>(&mut self, buf: B) -> (Result<usize>, B) {
+        let len = buf.bytes_init();
+
+        if self.buf.len() + len > self.capacity {
+            if let Err(err) = self.flush_buf().await {
+                return (Err(err), buf);
+            }
+        }
+
+        // Writes which don't fit in the buffer gain nothing from going through it
+        if len >= self.capacity {
+            return self.inner.write(buf).await;
+        }
+
+        let bytes = unsafe { std::slice::from_raw_parts(buf.stable_ptr(), len) };
+        self.buf.extend_from_slice(bytes);
+
+        (Ok(len), buf)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        self.flush_buf().await?;
+        self.inner.flush().await
+    }
+}
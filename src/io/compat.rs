@@ -0,0 +1,436 @@
+//! Poll based adapters over files and sockets, for libraries written against the IO traits
+//! of `futures-io` (feature `futures-io`) or `tokio` (feature `tokio-io`)
+//! 
+//! Completion based IO needs the buffer to stay put until the operation completes, which poll based
+//! callers can't guarantee since they may drop their buffer as soon as they get `Pending`. The adapter
+//! therefore does its IO out of buffers it owns:
+//! 
+//! - Reads fill an internal buffer, which callers then copy out of. A read is never lost, even if
+//!   the caller stops polling it.
+//! - Writes copy the caller's bytes into an internal buffer and stay pending until the kernel wrote them,
+//!   the caller must poll again with the same bytes. A write the caller stops polling still goes through,
+//!   and is awaited by the next write or flush.
+//! 
+//! Files with a cursor are read and written at the position the caller got to, bytes read ahead of it
+//! are only buffered and dropped by writes. The cursor of a file taken back with
+//! [`into_inner()`](Compat::into_inner) is where the caller left off.
+
+use std::io::Result;
+use std::future::Future;
+use std::net::Shutdown;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, ready};
+use std::mem;
+
+use crate::buf::{IoBuf, IoBufMut};
+use crate::fs::File;
+use crate::net::{TcpStream, UdpSocket};
+
+/// Number of bytes each read asks for
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+type ReadOp = Pin<Box<dyn Future<Output = (Result<usize>, Vec<u8>)>>>;
+type WriteOp = Pin<Box<dyn Future<Output = (Result<usize>, Vec<u8>)>>>;
+type ShutdownOp = Pin<Box<dyn Future<Output = Result<()>>>>;
+
+mod private {
+    use std::io::Result;
+
+    use crate::buf::{IoBuf, IoBufMut};
+
+    /// Operations the adapter performs, writes of datagram sockets must send the whole buffer
+    /// 
+    /// Reads and writes get an offset if the object has a [`position()`](Sealed::position).
+    #[allow(async_fn_in_trait)]
+    pub trait Sealed: 'static {
+        async fn read<B: IoBufMut>(&self, buf: B, offset: Option<u64>) -> (Result<usize>, B);
+        async fn write<B: IoBuf>(&self, buf: B, offset: Option<u64>) -> (Result<usize>, B);
+
+        /// Shuts down the writing side, if there is one
+        async fn shutdown(&self) -> Result<()> {
+            Ok(())
+        }
+
+        /// Offset the adapter starts at, for objects read and written at offsets
+        fn position(&self) -> Option<u64> {
+            None
+        }
+
+        /// Called once taken back, with the offset the adapter got to
+        fn set_position(&mut self, _pos: u64) {}
+    }
+}
+
+/// Types which can be wrapped in a [`Compat`]
+pub trait CompatIo: private::Sealed {}
+
+impl<T: private::Sealed> CompatIo for T {}
+
+/// Adapter implementing the poll based IO traits of `futures-io` and `tokio`, see the [module docs](self)
+/// 
+/// A `Compat<UdpSocket>` sends a datagram per write and reads datagrams as a stream of bytes,
+/// the socket must be connected.
+pub struct Compat<T: CompatIo> {
+    io: Rc<T>,
+
+    // Offset of `read_buf[read_pos]`, where the next write goes, for objects with a position
+    pos: Option<u64>,
+
+    // Bytes read and not yet consumed are `read_buf[read_pos..]`
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    read_op: Option<ReadOp>,
+
+    // Address and length of the caller's bytes the write in flight was copied from
+    write_src: (usize, usize),
+    write_buf: Vec<u8>,
+    write_op: Option<WriteOp>,
+    // Result of a write completed while reading, not returned to the caller yet
+    write_res: Option<Result<usize>>,
+
+    shutdown_op: Option<ShutdownOp>
+}
+
+impl<T: CompatIo> Compat<T> {
+    pub fn new(io: T) -> Self {
+        Self {
+            pos: io.position(),
+            io: Rc::new(io),
+            read_buf: Vec::new(),
+            read_pos: 0,
+            read_op: None,
+            write_src: (0, 0),
+            write_buf: Vec::new(),
+            write_op: None,
+            write_res: None,
+            shutdown_op: None
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Takes back the wrapped object, cancelling the operations in flight and discarding
+    /// buffered data which wasn't read yet
+    /// 
+    /// The cursor of a file is moved past the bytes consumed and written through the adapter.
+    pub fn into_inner(self) -> T {
+        let Self { io, pos, read_op, write_op, shutdown_op, .. } = self;
+
+        // The other references are held by the operations
+        drop((read_op, write_op, shutdown_op));
+
+        let mut io = Rc::into_inner(io).expect("operations outlived the adapter");
+
+        if let Some(pos) = pos {
+            io.set_position(pos);
+        }
+
+        io
+    }
+
+    fn poll_fill_buf(&mut self, cx: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+        if self.read_pos == self.read_buf.len() {
+            if self.pos.is_some() && self.write_op.is_some() {
+                // The read goes after the bytes of the write in flight
+                let res = ready!(self.poll_write_op(cx));
+                self.write_res = Some(res);
+            }
+
+            let op = self.read_op.get_or_insert_with(|| {
+                let io = self.io.clone();
+                let pos = self.pos;
+
+                let mut buf = mem::take(&mut self.read_buf);
+                buf.clear();
+                buf.reserve(READ_BUF_SIZE);
+                self.read_pos = 0;
+
+                Box::pin(async move { io.read(buf, pos).await })
+            });
+
+            let (res, buf) = ready!(op.as_mut().poll(cx));
+
+            self.read_op = None;
+            self.read_buf = buf;
+
+            if let Err(err) = res {
+                self.read_buf.clear();
+                return Poll::Ready(Err(err));
+            }
+        }
+
+        Poll::Ready(Ok(&self.read_buf[self.read_pos..]))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.read_buf.len() - self.read_pos);
+        self.read_pos += amt;
+
+        if let Some(pos) = &mut self.pos {
+            *pos += amt as u64;
+        }
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, data: &[u8]) -> Poll<Result<usize>> {
+        let src = (data.as_ptr() as usize, data.len());
+
+        // A write in flight holds the bytes of an earlier call which returned pending, its result is only
+        // returned when polled again with the same bytes. A caller which moved on to other bytes still
+        // can't take it back, so it completes before the new one starts.
+        if self.write_pending() && self.write_src != src {
+            ready!(self.poll_write_op(cx))?;
+        }
+
+        if !self.write_pending() {
+            if data.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+
+            if self.pos.is_some() {
+                // The bytes read ahead are at the offsets being written
+                self.read_op = None;
+                self.read_buf.clear();
+                self.read_pos = 0;
+            }
+
+            let io = self.io.clone();
+            let pos = self.pos;
+
+            let mut buf = mem::take(&mut self.write_buf);
+            buf.clear();
+            buf.extend_from_slice(data);
+
+            self.write_src = src;
+            self.write_op = Some(Box::pin(async move { io.write(buf, pos).await }));
+        }
+
+        self.poll_write_op(cx)
+    }
+
+    fn write_pending(&self) -> bool {
+        self.write_op.is_some() || self.write_res.is_some()
+    }
+
+    fn poll_write_op(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize>> {
+        if let Some(res) = self.write_res.take() {
+            return Poll::Ready(res);
+        }
+
+        let Some(op) = &mut self.write_op else {
+            return Poll::Ready(Ok(0));
+        };
+
+        let (res, buf) = ready!(op.as_mut().poll(cx));
+
+        self.write_op = None;
+        self.write_buf = buf;
+
+        if let (Some(pos), Ok(n)) = (&mut self.pos, &res) {
+            *pos += *n as u64;
+        }
+
+        Poll::Ready(res)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        // Nothing is buffered, only a write the caller stopped polling may be left
+        ready!(self.poll_write_op(cx))?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_flush(cx))?;
+
+        let op = self.shutdown_op.get_or_insert_with(|| {
+            let io = self.io.clone();
+            Box::pin(async move { io.shutdown().await })
+        });
+
+        let res = ready!(op.as_mut().poll(cx));
+        self.shutdown_op = None;
+
+        Poll::Ready(res)
+    }
+}
+
+impl private::Sealed for TcpStream {
+    async fn read<B: IoBufMut>(&self, buf: B, _offset: Option<u64>) -> (Result<usize>, B) {
+        TcpStream::read(self, buf).await
+    }
+
+    async fn write<B: IoBuf>(&self, buf: B, _offset: Option<u64>) -> (Result<usize>, B) {
+        TcpStream::write(self, buf).await
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        TcpStream::shutdown(self, Shutdown::Write).await
+    }
+}
+
+// Reads and writes may be in flight at once, so they can't go through the cursor. Files without one
+// use the kernel's file position.
+impl private::Sealed for File {
+    async fn read<B: IoBufMut>(&self, buf: B, offset: Option<u64>) -> (Result<usize>, B) {
+        match offset {
+            Some(offset) => File::read_at(self, buf, offset).await,
+            None => File::read_shared(self, buf).await
+        }
+    }
+
+    async fn write<B: IoBuf>(&self, buf: B, offset: Option<u64>) -> (Result<usize>, B) {
+        match offset {
+            Some(offset) => File::write_at(self, buf, offset).await,
+            None => File::write_shared(self, buf).await
+        }
+    }
+
+    fn position(&self) -> Option<u64> {
+        File::position(self)
+    }
+
+    fn set_position(&mut self, pos: u64) {
+        File::set_position(self, pos)
+    }
+}
+
+impl private::Sealed for UdpSocket {
+    async fn read<B: IoBufMut>(&self, buf: B, _offset: Option<u64>) -> (Result<usize>, B) {
+        UdpSocket::recv(self, buf).await
+    }
+
+    async fn write<B: IoBuf>(&self, buf: B, _offset: Option<u64>) -> (Result<usize>, B) {
+        UdpSocket::send(self, buf).await
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<T: CompatIo> futures_io::AsyncRead for Compat<T> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize>> {
+        let this = self.get_mut();
+        let data = ready!(this.poll_fill_buf(cx))?;
+        let n = data.len().min(buf.len());
+
+        buf[..n].copy_from_slice(&data[..n]);
+        this.consume(n);
+
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<T: CompatIo> futures_io::AsyncBufRead for Compat<T> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+        self.get_mut().poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume(amt)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<T: CompatIo> futures_io::AsyncWrite for Compat<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        self.get_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "tokio-io")]
+impl<T: CompatIo> tokio::io::AsyncRead for Compat<T> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        let data = ready!(this.poll_fill_buf(cx))?;
+        let n = data.len().min(buf.remaining());
+
+        buf.put_slice(&data[..n]);
+        this.consume(n);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio-io")]
+impl<T: CompatIo> tokio::io::AsyncBufRead for Compat<T> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+        self.get_mut().poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume(amt)
+    }
+}
+
+#[cfg(feature = "tokio-io")]
+impl<T: CompatIo> tokio::io::AsyncWrite for Compat<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+        self.get_mut().poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::io::SeekFrom;
+
+    use super::Compat;
+    use crate::fs::{self, File, OpenOptions};
+
+    #[test]
+    fn file_position_after_read_write() {
+        crate::init().unwrap();
+
+        crate::run(async {
+            let path = std::env::temp_dir().join(format!("uring-compat-{}", std::process::id()));
+            fs::write(&path, &b"0123456789"[..]).await.unwrap();
+
+            let file = File::open(&path, &OpenOptions::new().read(true).write(true)).await.unwrap();
+            let mut compat = Compat::new(file);
+
+            // The whole file is read ahead, only 4 bytes of it are consumed
+            let read = poll_fn(|cx| compat.poll_fill_buf(cx).map_ok(|data| data.to_vec())).await.unwrap();
+            assert_eq!(read, b"0123456789");
+            compat.consume(4);
+
+            let written = poll_fn(|cx| compat.poll_write(cx, b"ab")).await.unwrap();
+            assert_eq!(written, 2);
+
+            let read = poll_fn(|cx| compat.poll_fill_buf(cx).map_ok(|data| data.to_vec())).await.unwrap();
+            assert_eq!(read, b"6789");
+            compat.consume(1);
+
+            let mut file = compat.into_inner();
+            assert_eq!(file.seek(SeekFrom::Current(0)).await.unwrap(), 7);
+
+            let (res, buf) = file.read(Vec::with_capacity(16)).await;
+            res.unwrap();
+            assert_eq!(buf, b"789");
+
+            assert_eq!(fs::read(&path).await.unwrap(), b"0123ab6789");
+            fs::remove_file(&path).await.unwrap();
+        });
+    }
+}
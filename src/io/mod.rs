@@ -0,0 +1,189 @@
+//! Traits for reading and writing owned buffers, implemented by files and streams
+//! 
+//! Like the inherent methods they forward to, reads and writes take ownership of the buffer
+//! and hand it back along with the result, see [`buf`](crate::buf).
+
+mod buf_reader;
+mod buf_writer;
+
+#[cfg(any(feature = "futures-io", feature = "tokio-io"))]
+pub mod compat;
+
+pub use buf_reader::{BufReader, Lines};
+pub use buf_writer::BufWriter;
+
+use std::io::{Result, Error, ErrorKind};
+
+use crate::buf::{IoBuf, IoBufMut};
+use crate::fs::File;
+use crate::net::{TcpStream, OwnedReadHalf, OwnedWriteHalf};
+
+/// Size of the buffers of [`BufReader`] and [`BufWriter`]
+const DEFAULT_BUF_SIZE: usize = 8 * 1024;
+
+/// Number of bytes `read_to_end` reserves whenever the buffer is full
+const READ_CHUNK: usize = 8 * 1024;
+
+/// Size of the buffer used by [`copy()`]
+const COPY_BUF_SIZE: usize = 64 * 1024;
+
+/// Source of bytes which are read into owned buffers
+// The runtime is single threaded, its futures never need to be Send
+#[allow(async_fn_in_trait)]
+pub trait AsyncRead {
+    /// Reads into the buffer, returning the number of bytes read along with the buffer,
+    /// 0 meaning that the end of the source was reached
+    async fn read<B: IoBufMut>(&mut self, buf: B) -> (Result<usize>, B);
+
+    /// Reads until the whole buffer is filled, failing with `UnexpectedEof` if the source ends before that
+    async fn read_exact<B: IoBufMut>(&mut self, buf: B) -> (Result<()>, B) {
+        let mut buf = buf.slice(..);
+        let mut filled = 0;
+
+        while filled < buf.end() {
+            let (res, slice) = self.read(buf.into_inner().slice(filled..)).await;
+            buf = slice;
+
+            match res {
+                Ok(0) => return (Err(Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer")), buf.into_inner()),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {},
+                Err(err) => return (Err(err), buf.into_inner())
+            }
+        }
+
+        (Ok(()), buf.into_inner())
+    }
+
+    /// Reads until the end of the source, appending the bytes read to the vector
+    /// 
+    /// Returns the number of bytes read along with the vector.
+    async fn read_to_end(&mut self, mut buf: Vec<u8>) -> (Result<usize>, Vec<u8>) {
+        let start = buf.len();
+
+        loop {
+            if buf.len() == buf.capacity() {
+                buf.reserve(READ_CHUNK);
+            }
+
+            let len = buf.len();
+            let (res, slice) = self.read(buf.slice(len..)).await;
+            buf = slice.into_inner();
+
+            match res {
+                Ok(0) => return (Ok(buf.len() - start), buf),
+                Ok(_) => {},
+                Err(err) if err.kind() == ErrorKind::Interrupted => {},
+                Err(err) => return (Err(err), buf)
+            }
+        }
+    }
+}
+
+/// Sink of bytes which are written out of owned buffers
+#[allow(async_fn_in_trait)]
+pub trait AsyncWrite {
+    /// Writes the buffer's initialized bytes, returning the number of bytes written along with the buffer
+    async fn write<B: IoBuf>(&mut self, buf: B) -> (Result<usize>, B);
+
+    /// Writes out any data buffered in userspace, there is none unless the writer is a buffered wrapper
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writes until all of the buffer's initialized bytes were written, failing with `WriteZero`
+    /// if the sink stops accepting bytes before that
+    async fn write_all<B: IoBuf>(&mut self, buf: B) -> (Result<()>, B) {
+        let len = buf.bytes_init();
+        let mut buf = buf.slice(..);
+        let mut written = 0;
+
+        while written < len {
+            let (res, slice) = self.write(buf.into_inner().slice(written..)).await;
+            buf = slice;
+
+            match res {
+                Ok(0) => return (Err(Error::new(ErrorKind::WriteZero, "failed to write whole buffer")), buf.into_inner()),
+                Ok(n) => written += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {},
+                Err(err) => return (Err(err), buf.into_inner())
+            }
+        }
+
+        (Ok(()), buf.into_inner())
+    }
+}
+
+/// Copies the whole of the reader into the writer, returning the number of bytes copied
+/// 
+/// The writer isn't flushed. Use [`fs::copy()`](crate::fs::copy) to copy files, which doesn't go through userspace.
+pub async fn copy<R: AsyncRead + ?Sized, W: AsyncWrite + ?Sized>(reader: &mut R, writer: &mut W) -> Result<u64> {
+    let mut buf = Vec::with_capacity(COPY_BUF_SIZE);
+    let mut copied = 0;
+
+    loop {
+        buf.clear();
+
+        let (res, read_buf) = reader.read(buf).await;
+        buf = read_buf;
+
+        match res {
+            Ok(0) => return Ok(copied),
+            Ok(n) => copied += n as u64,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err)
+        }
+
+        let (res, write_buf) = writer.write_all(buf).await;
+        buf = write_buf;
+        res?;
+    }
+}
+
+impl AsyncRead for TcpStream {
+    async fn read<B: IoBufMut>(&mut self, buf: B) -> (Result<usize>, B) {
+        TcpStream::read(self, buf).await
+    }
+}
+
+impl AsyncRead for &TcpStream {
+    async fn read<B: IoBufMut>(&mut self, buf: B) -> (Result<usize>, B) {
+        TcpStream::read(self, buf).await
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    async fn write<B: IoBuf>(&mut self, buf: B) -> (Result<usize>, B) {
+        TcpStream::write(self, buf).await
+    }
+}
+
+impl AsyncWrite for &TcpStream {
+    async fn write<B: IoBuf>(&mut self, buf: B) -> (Result<usize>, B) {
+        TcpStream::write(self, buf).await
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    async fn read<B: IoBufMut>(&mut self, buf: B) -> (Result<usize>, B) {
+        OwnedReadHalf::read(self, buf).await
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    async fn write<B: IoBuf>(&mut self, buf: B) -> (Result<usize>, B) {
+        OwnedWriteHalf::write(self, buf).await
+    }
+}
+
+impl AsyncRead for File {
+    async fn read<B: IoBufMut>(&mut self, buf: B) -> (Result<usize>, B) {
+        File::read(self, buf).await
+    }
+}
+
+impl AsyncWrite for File {
+    async fn write<B: IoBuf>(&mut self, buf: B) -> (Result<usize>, B) {
+        File::write(self, buf).await
+    }
+}
@@ -1,20 +1,21 @@
+use std::rc::Rc;
 use std::pin::Pin;
-use std::cell::Cell;
 use std::future::Future;
 use std::marker::PhantomData;
 use std::task::{Context, Poll};
 
-use crate::{RUNTIME, runtime::TaskId};
+use crate::{RUNTIME, runtime::TaskId, error::JoinError};
 
 
 
 /// Handle to a task which can be awaited on
 /// The handle does not need to be awaited for the task to run. IF the handle is dropped, the task will still run
 /// 
+/// Awaiting the handle returns the task's output, or [`JoinError::Cancelled`] if the task was aborted.
+/// 
 /// This is returned by [`spawn()`]
 pub struct JoinHandle<T: 'static> {
     id: TaskId,
-    registered: Cell<bool>,
     phantom: PhantomData<T>
 }
 
@@ -22,29 +23,35 @@ impl<T> JoinHandle<T> {
     pub(crate) fn new(id: TaskId) -> Self {
         Self {
             id,
-            registered: Cell::new(false),
             phantom: PhantomData
         }
     }
+
+    /// Aborts the task, see [`AbortHandle::abort()`]
+    pub fn abort(&self) {
+        abort_task(self.id);
+    }
+
+    /// Returns a handle which can abort the task without owning the join handle
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle { id: self.id, phantom: PhantomData }
+    }
 }
 
 impl<T> Future for JoinHandle<T> {
-    type Output = T;
+    type Output = Result<T, JoinError>;
 
-    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let res = RUNTIME.with_borrow_mut(|rt| rt.pop_join_handle_result(self.id));
 
         match res {
-            Some(res) => {
+            Some(Ok(res)) => {
                 let res = *res.downcast::<T>().expect("Type error when downcasting task result");
-                Poll::Ready(res)
+                Poll::Ready(Ok(res))
             },
+            Some(Err(err)) => Poll::Ready(Err(err)),
             None => {
-                if !self.registered.get() {
-                    RUNTIME.with_borrow_mut(|rt| rt.register_join_handle_wakeup(self.id));
-                    self.registered.set(true);
-                }
-
+                RUNTIME.with_borrow_mut(|rt| rt.register_join_handle_wakeup(self.id, cx.waker()));
                 Poll::Pending
             }
         }
@@ -55,4 +62,38 @@ impl<T> Drop for JoinHandle<T> {
     fn drop(&mut self) {
         RUNTIME.with_borrow_mut(|rt| rt.drop_join_handle(self.id));
     }
-}
\ No newline at end of file
+}
+
+/// Handle which can abort a task, this can be cloned and held independently of the task's [`JoinHandle`]
+/// 
+/// This is returned by [`JoinHandle::abort_handle()`]
+#[derive(Clone)]
+pub struct AbortHandle {
+    id: TaskId,
+    // Aborting touches the thread-local runtime, so the handle must stay on its thread
+    phantom: PhantomData<Rc<()>>
+}
+
+impl AbortHandle {
+    /// Aborts the task
+    /// 
+    /// The task is removed from the runtime and its future is dropped, cancelling any IO
+    /// it was waiting on. Awaiting its join handle then returns [`JoinError::Cancelled`].
+    /// 
+    /// A task may abort itself, in which case it is dropped once its current poll returns.
+    /// Aborting a task which has already finished does nothing.
+    pub fn abort(&self) {
+        abort_task(self.id);
+    }
+}
+
+fn abort_task(id: TaskId) {
+    let (task, waker) = RUNTIME.with_borrow_mut(|rt| rt.abort_task(id));
+
+    // Drop the task outside the runtime borrow, as dropping its IO futures cancels them
+    drop(task);
+
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
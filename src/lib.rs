@@ -4,39 +4,50 @@ mod error;
 mod runtime;
 mod platform;
 mod join_handle;
+mod waker;
+mod builder;
 
+pub mod buf;
 pub mod fs;
+pub mod io;
 pub mod time;
 pub mod net;
 pub mod util;
-pub use join_handle::JoinHandle;
+pub use join_handle::{JoinHandle, AbortHandle};
+pub use builder::Builder;
 
-use std::ptr;
 use std::pin::pin;
 use std::future::Future;
 use std::cell::{Cell, RefCell};
-use std::task::{Poll, Context, Waker, RawWaker, RawWakerVTable};
+use std::task::{Poll, Context};
+use std::panic::{self, AssertUnwindSafe};
 
-pub use error::UringError;
-use runtime::{Runtime, WokenTask};
+pub use error::{UringError, JoinError};
+pub use runtime::PanicPolicy;
+use runtime::WokenTask;
 
 
 thread_local! {
     // The lazy initializer panics, therefore enforcing that the runtime is initialized only using init()
     // This works because init() uses .set() which does not run the lazy initializer
-    pub(crate) static RUNTIME: RefCell<Runtime> = panic!("init() has not been called on this thread!");
+    pub(crate) static RUNTIME: RefCell<runtime::Runtime> = panic!("init() has not been called on this thread!");
 
     pub(crate) static RUNNING: Cell<bool> = const { Cell::new(false) };
 }
 
-static WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(|_| panic!(), |_| (), |_| (), |_| ());
-
-/// Initializes the thread-local runtime
+/// Initializes the thread-local runtime with the default configuration
 /// 
-/// This must be called at least once before calling [`run()`] on a thread
+/// This must be called at least once before calling [`run()`] on a thread,
+/// use [`Builder`] instead to configure the runtime
 pub fn init() -> Result<(), UringError> {
-    RUNTIME.set(Runtime::new()?);
-    Ok(())
+    Builder::new().init()
+}
+
+/// Sets what happens when a spawned task panics, see [`PanicPolicy`]
+/// 
+/// The policy is kept for all following [`run()`] calls on this thread
+pub fn set_panic_policy(policy: PanicPolicy) {
+    RUNTIME.with_borrow_mut(|rt| rt.panic_policy = policy);
 }
 
 /// Runs a future on the current thread, blocking it whenever waiting for IO
@@ -45,11 +56,14 @@ pub fn init() -> Result<(), UringError> {
 /// All child tasks must finish before the root task finishes, otherwise they will be dropped.
 /// If necessary for the root task to wait for a child task, it can await on the child's 
 /// [`JoinHandle`] returned by [`spawn()`].
+/// 
+/// If a child task panics, it is handled according to the thread's [`PanicPolicy`].
+/// If the root task panics, the runtime is reset and the panic is resumed.
 pub fn run<F: Future>(root_task: F) -> F::Output {
     RUNNING.set(true);
 
-    let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &WAKER_VTABLE)) };
-    let mut cx = Context::from_waker(&waker);
+    let root_waker = RUNTIME.with_borrow(|rt| rt.task_waker(0));
+    let mut root_cx = Context::from_waker(&root_waker);
     
     let mut root_task = pin!(root_task);
 
@@ -61,26 +75,61 @@ pub fn run<F: Future>(root_task: F) -> F::Output {
             match task {
                 // Root task woken up
                 Some(WokenTask::Root) => {
-                    let poll = root_task.as_mut().poll(&mut cx);
+                    let poll = panic::catch_unwind(AssertUnwindSafe(|| root_task.as_mut().poll(&mut root_cx)));
 
-                    // Root task finished, reset runtime and return
-                    if let Poll::Ready(res) = poll {
-                        RUNTIME.with_borrow_mut(|rt| rt.reset());
-                        RUNNING.set(false);
-                        return res;
+                    match poll {
+                        Ok(Poll::Pending) => (),
+
+                        // Root task finished, reset runtime and return
+                        Ok(Poll::Ready(res)) => {
+                            reset();
+                            RUNNING.set(false);
+                            return res;
+                        },
+
+                        // Root task panicked, reset runtime so it can be reused and keep unwinding
+                        Err(payload) => {
+                            reset();
+                            RUNNING.set(false);
+                            panic::resume_unwind(payload);
+                        }
                     }
                 },
     
                 // Child task woken up
                 Some(WokenTask::Child(mut task)) => {
-                    let poll = task.as_mut().poll(&mut cx);
+                    let poll = panic::catch_unwind(AssertUnwindSafe(|| task.poll()));
 
                     match poll {
-                        // Child task pending, return it into task list
-                        Poll::Pending => RUNTIME.with_borrow_mut(|rt| rt.return_task(task)),
+                        // Child task pending, return it into task list, unless it aborted itself
+                        // while being polled, in which case it is dropped here outside the runtime borrow
+                        Ok(Poll::Pending) => {
+                            let aborted = RUNTIME.with_borrow_mut(|rt| rt.return_task(task));
+                            drop(aborted);
+                        },
                         
-                        // Child task finished with result
-                        Poll::Ready(res) => RUNTIME.with_borrow_mut(|rt| rt.task_finished(res))
+                        // Child task finished with result, wake up the task waiting on it, if any
+                        Ok(Poll::Ready(res)) => {
+                            let waiting = RUNTIME.with_borrow_mut(|rt| rt.task_finished(res));
+
+                            if let Some(waker) = waiting {
+                                waker.wake();
+                            }
+                        },
+
+                        // Child task panicked, drop it and pass the panic on to its join handle
+                        Err(payload) => {
+                            if RUNTIME.with_borrow(|rt| rt.panic_policy) == PanicPolicy::Abort {
+                                std::process::abort();
+                            }
+
+                            drop(task);
+                            let waiting = RUNTIME.with_borrow_mut(|rt| rt.task_panicked(payload));
+
+                            if let Some(waker) = waiting {
+                                waker.wake();
+                            }
+                        }
                     }
                 },
 
@@ -90,10 +139,23 @@ pub fn run<F: Future>(root_task: F) -> F::Output {
         }
 
         // Wait for IO events to wake up more tasks
-        RUNTIME.with_borrow_mut(|rt| rt.wait_for_io());
+        let wakers = RUNTIME.with_borrow_mut(|rt| rt.wait_for_io());
+
+        for waker in wakers {
+            waker.wake();
+        }
     }
 }
 
+/// Drops all remaining tasks and waits for their IO to be cancelled
+fn reset() {
+    // Tasks are dropped outside of the runtime borrow, as dropping them cancels their IO
+    let tasks = RUNTIME.with_borrow_mut(|rt| rt.reset());
+    drop(tasks);
+
+    RUNTIME.with_borrow_mut(|rt| rt.plat.reset());
+}
+
 pub fn spawn<F: Future + 'static>(task: F) -> JoinHandle<F::Output> {
     if !RUNNING.get() {
         panic!("spawn() called outside of a run() call!")
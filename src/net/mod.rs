@@ -1,5 +1,7 @@
 mod udp;
 mod tcp;
+mod split;
 
 pub use udp::UdpSocket;
-pub use tcp::{TcpListener, TcpStream};
\ No newline at end of file
+pub use tcp::{TcpListener, TcpStream, RecvStream, Incoming};
+pub use split::{OwnedReadHalf, OwnedWriteHalf, ReuniteError};
\ No newline at end of file
@@ -0,0 +1,125 @@
+use std::io::Result;
+use std::net::Shutdown;
+use std::rc::Rc;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::buf::{IoBuf, IoBufMut, BufRing, ProvidedBuf};
+use crate::platform::socket_shutdown;
+use super::tcp::{TcpStream, RecvStream};
+
+/// Reading half of a [`TcpStream`], see [`TcpStream::into_split()`]
+pub struct OwnedReadHalf {
+    stream: Rc<TcpStream>
+}
+
+/// Writing half of a [`TcpStream`], see [`TcpStream::into_split()`]
+/// 
+/// Dropping it shuts down the writing side of the stream, unless it was already shut down.
+pub struct OwnedWriteHalf {
+    stream: Rc<TcpStream>,
+    shutdown_on_drop: bool
+}
+
+/// Error returned when reuniting halves of different streams, holding the halves
+#[derive(Error)]
+#[error("Tried to reunite halves of different streams")]
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+impl fmt::Debug for ReuniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReuniteError").finish_non_exhaustive()
+    }
+}
+
+/// Splits the stream into halves sharing it
+pub(super) fn split(stream: TcpStream) -> (OwnedReadHalf, OwnedWriteHalf) {
+    let stream = Rc::new(stream);
+
+    (
+        OwnedReadHalf { stream: stream.clone() },
+        OwnedWriteHalf { stream, shutdown_on_drop: true }
+    )
+}
+
+impl OwnedReadHalf {
+    pub fn std(&self) -> &std::net::TcpStream {
+        self.stream.std()
+    }
+
+    /// Reads into the buffer, see [`TcpStream::read()`]
+    pub async fn read<B: IoBufMut>(&self, buf: B) -> (Result<usize>, B) {
+        self.stream.read(buf).await
+    }
+
+    /// Reads into the buffers in order, see [`TcpStream::read_vectored()`]
+    pub async fn read_vectored<B: IoBufMut>(&self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        self.stream.read_vectored(bufs).await
+    }
+
+    /// Reads into a buffer picked from the ring, see [`TcpStream::read_provided()`]
+    pub async fn read_provided(&self, ring: &BufRing) -> Result<Option<ProvidedBuf>> {
+        self.stream.read_provided(ring).await
+    }
+
+    /// Returns a stream of the data received, see [`TcpStream::read_multishot()`]
+    pub fn read_multishot<'a>(&'a self, ring: &BufRing) -> RecvStream<'a> {
+        self.stream.read_multishot(ring)
+    }
+
+    /// Puts the stream back together, fails if the halves come from different streams
+    pub fn reunite(self, mut other: OwnedWriteHalf) -> std::result::Result<TcpStream, ReuniteError> {
+        if !Rc::ptr_eq(&self.stream, &other.stream) {
+            return Err(ReuniteError(self, other));
+        }
+
+        other.shutdown_on_drop = false;
+        drop(other);
+
+        Ok(Rc::into_inner(self.stream).expect("halves of the stream were reunited twice"))
+    }
+}
+
+impl OwnedWriteHalf {
+    pub fn std(&self) -> &std::net::TcpStream {
+        self.stream.std()
+    }
+
+    /// Writes the buffer's initialized bytes, see [`TcpStream::write()`]
+    pub async fn write<B: IoBuf>(&self, buf: B) -> (Result<usize>, B) {
+        self.stream.write(buf).await
+    }
+
+    /// Writes the initialized bytes of the buffers in order, see [`TcpStream::write_vectored()`]
+    pub async fn write_vectored<B: IoBuf>(&self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        self.stream.write_vectored(bufs).await
+    }
+
+    /// Writes the buffer's initialized bytes without copying them, see [`TcpStream::write_zc()`]
+    pub async fn write_zc<B: IoBuf>(&self, buf: B) -> (Result<(usize, bool)>, B) {
+        self.stream.write_zc(buf).await
+    }
+
+    /// Shuts down the writing side of the stream, which then isn't shut down again when dropped
+    pub async fn shutdown(&mut self) -> Result<()> {
+        socket_shutdown(&self.stream.0, Shutdown::Write).await?;
+        self.shutdown_on_drop = false;
+        Ok(())
+    }
+
+    /// Puts the stream back together, fails if the halves come from different streams
+    pub fn reunite(self, other: OwnedReadHalf) -> std::result::Result<TcpStream, ReuniteError> {
+        other.reunite(self)
+    }
+}
+
+impl Drop for OwnedWriteHalf {
+    fn drop(&mut self) {
+        // shutdown doesn't block, doing it right away rather than through the ring makes sure
+        // it happens before the socket is closed
+        if self.shutdown_on_drop {
+            let _ = self.stream.0.shutdown(Shutdown::Write);
+        }
+    }
+}
@@ -1,21 +1,36 @@
 use std::io::Result;
+use std::rc::Rc;
+
+use crate::buf::{IoBuf, IoBufMut, BufRing, ProvidedBuf, RingShared};
 use std::net::{SocketAddr, ToSocketAddrs, Shutdown};
-use std::mem::ManuallyDrop;
+
+use super::split::{split, OwnedReadHalf, OwnedWriteHalf};
 
 use crate::{
     util::try_zip,
     platform::{
+        Handle,
+        UringStream,
+        OpensFd,
+        provided_buf,
+        socket_recv_provided,
+        socket_recv_multi,
         socket_create,
-        socket_close,
         socket_connect,
         socket_recv,
+        socket_recv_vectored,
+        socket_send_vectored,
         socket_send,
+        socket_send_zc,
         socket_accept,
+        socket_accept_multi,
+        multishot_direct,
+        take_slot,
         socket_shutdown,
     }
 };
 
-pub struct TcpStream(ManuallyDrop<std::net::TcpStream>);
+pub struct TcpStream(pub(super) Handle<std::net::TcpStream>);
 
 impl TcpStream {
     pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
@@ -26,18 +41,13 @@ impl TcpStream {
             socket_create::<std::net::TcpStream>(true, false)
         ).await?;
 
-        // Prevent the streams from being auto dropped since we need to manually drop them
-        let stream_v4 = ManuallyDrop::new(stream_v4);
-        let stream_v6 = ManuallyDrop::new(stream_v6);
-
         let mut res = None;
 
         for addr in addr_iter {
             match addr {
                 SocketAddr::V4(_) => {
-                    match socket_connect(&*stream_v4, &addr).await {
+                    match socket_connect(&stream_v4, &addr).await {
                         Ok(()) => {
-                            socket_close(&*stream_v6);
                             stream_v4.set_nonblocking(true)?;
                             return Ok(Self(stream_v4));
                         },
@@ -47,9 +57,8 @@ impl TcpStream {
                 },
 
                 SocketAddr::V6(_) => {
-                    match socket_connect(&*stream_v6, &addr).await {
+                    match socket_connect(&stream_v6, &addr).await {
                         Ok(()) => {
-                            socket_close(&*stream_v4);
                             stream_v6.set_nonblocking(true)?;
                             return Ok(Self(stream_v6));
                         },
@@ -70,33 +79,116 @@ impl TcpStream {
         &self.0
     }
 
-    pub async fn read(&self, buf: &mut [u8]) -> Result<usize> {
-        socket_recv(&*self.0, buf, false).await
+    /// Reads into the buffer, returning the number of bytes read along with the buffer
+    pub async fn read<B: IoBufMut>(&self, buf: B) -> (Result<usize>, B) {
+        socket_recv(&self.0, buf, false).await
+    }
+
+    /// Writes the buffer's initialized bytes, returning the number of bytes written along with the buffer
+    pub async fn write<B: IoBuf>(&self, buf: B) -> (Result<usize>, B) {
+        socket_send(&self.0, buf).await
+    }
+
+    /// Reads into the buffers in order, returning the number of bytes read along with the buffers
+    pub async fn read_vectored<B: IoBufMut>(&self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        socket_recv_vectored(&self.0, bufs).await
+    }
+
+    /// Writes the initialized bytes of the buffers in order, returning the number of bytes written along with the buffers
+    pub async fn write_vectored<B: IoBuf>(&self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        socket_send_vectored(&self.0, bufs).await
+    }
+
+    /// Writes the buffer's initialized bytes without copying them into the kernel, returning the number
+    /// of bytes written and whether the kernel actually avoided the copy, along with the buffer
+    /// 
+    /// The buffer is only handed back once the kernel no longer uses it, which may be well after the data
+    /// was written. Zero-copy only pays off for large buffers, and the kernel falls back to copying for
+    /// some destinations (e.g. loopback).
+    pub async fn write_zc<B: IoBuf>(&self, buf: B) -> (Result<(usize, bool)>, B) {
+        socket_send_zc(&self.0, buf).await
     }
 
-    pub async fn write(&self, buf: &[u8]) -> Result<usize> {
-        socket_send(&*self.0, buf).await
+    /// Reads into a buffer the kernel picks from the ring once data arrives,
+    /// returns `None` once the peer closed the connection
+    pub async fn read_provided(&self, ring: &BufRing) -> Result<Option<ProvidedBuf>> {
+        socket_recv_provided(&self.0, ring.shared()).await
+    }
+
+    /// Returns a stream of the data received on the socket, using a single multishot recv which reads into buffers
+    /// the kernel picks from the ring
+    /// 
+    /// Nothing is received until the stream is first polled.
+    pub fn read_multishot<'a>(&'a self, ring: &BufRing) -> RecvStream<'a> {
+        RecvStream { stream: self, ring: ring.shared().clone(), op: None, done: false }
     }
 
     pub async fn shutdown(&self, how: Shutdown) -> Result<()> {
-        socket_shutdown(&*self.0, how).await
+        socket_shutdown(&self.0, how).await
     }
+
+    /// Splits the stream into a reading and a writing half, which can be moved into different tasks
+    /// 
+    /// Dropping the writing half shuts down the writing side of the stream, the halves can be put back
+    /// together with [`OwnedReadHalf::reunite()`].
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        split(self)
+    }
+}
+
+/// Stream of the data received on a [`TcpStream`], see [`TcpStream::read_multishot()`]
+pub struct RecvStream<'a> {
+    stream: &'a TcpStream,
+    ring: Rc<RingShared>,
+    op: Option<UringStream<Rc<RingShared>>>,
+    done: bool
 }
 
-impl Drop for TcpStream {
-    fn drop(&mut self) {
-        socket_close(&*self.0);
+impl RecvStream<'_> {
+    /// Waits for the next buffer of data, returns `None` once the peer closed the connection
+    /// 
+    /// Returns an error with `ENOBUFS` if the ring ran out of buffers, the stream can be polled again
+    /// once some buffers were dropped.
+    pub async fn next(&mut self) -> Option<Result<ProvidedBuf>> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let op = self.op.get_or_insert_with(|| socket_recv_multi(&self.stream.0, &self.ring));
+
+            let cqe = match op.next().await {
+                Some(cqe) => cqe,
+
+                // The kernel ended the recv, start another one
+                None => {
+                    self.op = None;
+                    continue;
+                }
+            };
+
+            match provided_buf(self.ring.clone(), cqe) {
+                Ok(Some(buf)) => return Some(Ok(buf)),
+
+                Ok(None) => {
+                    self.done = true;
+                    return None;
+                },
+
+                Err(err) => return Some(Err(err))
+            }
+        }
     }
 }
 
-pub struct TcpListener(ManuallyDrop<std::net::TcpListener>);
+pub struct TcpListener(Handle<std::net::TcpListener>);
 
 impl TcpListener {
     pub async fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
         let listener = std::net::TcpListener::bind(addr)?;
         listener.set_nonblocking(true)?;
 
-        Ok(Self(ManuallyDrop::new(listener)))
+        Ok(Self(Handle::new(listener)))
     }
 
     pub fn std(&self) -> &std::net::TcpListener {
@@ -104,15 +196,57 @@ impl TcpListener {
     }
 
     pub async fn accept(&self) -> Result<(TcpStream, SocketAddr)> {
-        let res = socket_accept(&*self.0).await;
+        let res = socket_accept(&self.0).await;
 
         // Map from std TcpStream to our own TcpStream type
-        res.map(|(stream, addr)| (TcpStream(ManuallyDrop::new(stream)), addr))
+        res.map(|(stream, addr)| (TcpStream(stream), addr))
+    }
+
+    /// Returns a stream of incoming connections, using a single multishot accept
+    /// 
+    /// Nothing is accepted until the stream is first polled.
+    pub fn incoming(&self) -> Incoming<'_> {
+        Incoming { listener: self, op: None, direct: false }
     }
-} 
+}
+
+/// Stream of the connections accepted by a [`TcpListener`], see [`TcpListener::incoming()`]
+pub struct Incoming<'a> {
+    listener: &'a TcpListener,
+    op: Option<UringStream<OpensFd>>,
 
-impl Drop for TcpListener {
-    fn drop(&mut self) {
-        socket_close(&*self.0);
+    // Whether the accept places connections in the registered files table
+    direct: bool
+}
+
+impl Incoming<'_> {
+    /// Waits for the next connection
+    /// 
+    /// The listener accepts connections until it's dropped, so the stream doesn't end and this always
+    /// returns `Some`, failed accepts are returned as errors.
+    pub async fn next(&mut self) -> Option<Result<TcpStream>> {
+        loop {
+            let op = match &mut self.op {
+                Some(op) => op,
+                None => {
+                    self.direct = multishot_direct();
+                    self.op.insert(socket_accept_multi(&self.listener.0, self.direct))
+                }
+            };
+
+            match op.next().await {
+                Some(cqe) => {
+                    if self.direct {
+                        take_slot();
+                    }
+
+                    let res = Handle::opened(cqe.result, self.direct).await;
+                    return Some(res.map(TcpStream));
+                },
+
+                // The kernel ended the accept, start another one
+                None => self.op = None
+            }
+        }
     }
 } 
\ No newline at end of file
@@ -1,24 +1,29 @@
 use std::io::Result;
-use std::mem::ManuallyDrop;
+
+use crate::buf::{IoBuf, IoBufMut};
 use std::net::{SocketAddr, ToSocketAddrs};
 
 use crate::platform::{
+    Handle,
     socket_recv,
+    socket_recv_vectored,
+    socket_send_vectored,
     socket_recv_from,
     socket_send,
     socket_send_to,
+    socket_send_zc,
+    socket_send_to_zc,
     socket_connect,
-    socket_close,
 };
 
-pub struct UdpSocket(ManuallyDrop<std::net::UdpSocket>);
+pub struct UdpSocket(Handle<std::net::UdpSocket>);
 
 impl UdpSocket {
     pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self> {
         let socket = std::net::UdpSocket::bind(addr)?;
         socket.set_nonblocking(true)?;
 
-        Ok(Self(ManuallyDrop::new(socket)))
+        Ok(Self(Handle::new(socket)))
     }
 
     pub fn std(&self) -> &std::net::UdpSocket {
@@ -31,7 +36,7 @@ impl UdpSocket {
         let mut res = None;
 
         for addr in addr_iter {
-            match socket_connect(&*self.0, &addr).await {
+            match socket_connect(&self.0, &addr).await {
                 Ok(()) => return Ok(()),
                 Err(err) => res = Some(err)
             }
@@ -44,39 +49,59 @@ impl UdpSocket {
     }
 
 
-    pub async fn recv(&self, buf: &mut [u8]) -> Result<usize> {
-        socket_recv(&*self.0, buf, false).await
+    pub async fn recv<B: IoBufMut>(&self, buf: B) -> (Result<usize>, B) {
+        socket_recv(&self.0, buf, false).await
+    }
+
+    pub async fn recv_from<B: IoBufMut>(&self, buf: B) -> (Result<(usize, SocketAddr)>, B) {
+        socket_recv_from(&self.0, buf, false).await
+    }
+
+    pub async fn peek<B: IoBufMut>(&self, buf: B) -> (Result<usize>, B) {
+        socket_recv(&self.0, buf, true).await
     }
 
-    pub async fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
-        socket_recv_from(&*self.0, buf, false).await
+    pub async fn peek_from<B: IoBufMut>(&self, buf: B) -> (Result<(usize, SocketAddr)>, B) {
+        socket_recv_from(&self.0, buf, true).await
     }
 
-    pub async fn peek(&self, buf: &mut [u8]) -> Result<usize> {
-        socket_recv(&*self.0, buf, true).await
+    pub async fn send<B: IoBuf>(&self, buf: B) -> (Result<usize>, B) {
+        socket_send(&self.0, buf).await
     }
 
-    pub async fn peek_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
-        socket_recv_from(&*self.0, buf, true).await
+    /// Receives a datagram into the buffers in order, returning the number of bytes received along with the buffers
+    pub async fn recv_vectored<B: IoBufMut>(&self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        socket_recv_vectored(&self.0, bufs).await
     }
 
-    pub async fn send(&self, buf: &[u8]) -> Result<usize> {
-        socket_send(&*self.0, buf).await
+    /// Sends the initialized bytes of the buffers as a single datagram, returning the number of bytes sent along with the buffers
+    pub async fn send_vectored<B: IoBuf>(&self, bufs: Vec<B>) -> (Result<usize>, Vec<B>) {
+        socket_send_vectored(&self.0, bufs).await
     }
 
-    pub async fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> Result<usize> {
+    pub async fn send_to<B: IoBuf, A: ToSocketAddrs>(&self, buf: B, addr: A) -> (Result<usize>, B) {
         let addr = addr
             .to_socket_addrs()
             .expect("Couldn't get address iterator")
             .next()
             .expect("Address iterator didn't provide any addresses");
 
-        socket_send_to(&*self.0, buf, &addr).await
+        socket_send_to(&self.0, buf, &addr).await
     }
-}
 
-impl Drop for UdpSocket {
-    fn drop(&mut self) {
-        socket_close(&*self.0);
+    /// Sends the buffer without copying it into the kernel, see [`TcpStream::write_zc()`](super::TcpStream::write_zc)
+    pub async fn send_zc<B: IoBuf>(&self, buf: B) -> (Result<(usize, bool)>, B) {
+        socket_send_zc(&self.0, buf).await
     }
-}
\ No newline at end of file
+
+    /// Sends the buffer to the address without copying it into the kernel, see [`TcpStream::write_zc()`](super::TcpStream::write_zc)
+    pub async fn send_to_zc<B: IoBuf, A: ToSocketAddrs>(&self, buf: B, addr: A) -> (Result<(usize, bool)>, B) {
+        let addr = addr
+            .to_socket_addrs()
+            .expect("Couldn't get address iterator")
+            .next()
+            .expect("Address iterator didn't provide any addresses");
+
+        socket_send_to_zc(&self.0, buf, &addr).await
+    }
+}
@@ -0,0 +1,144 @@
+use std::thread;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Condvar, OnceLock};
+use std::future::Future;
+use std::time::Duration;
+use std::collections::VecDeque;
+use std::task::{Context, Poll, Waker};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+const MAX_THREADS: usize = 16;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pool of threads running blocking work which has no io_uring equivalent
+/// 
+/// Threads are spawned on demand, up to [`MAX_THREADS`], and exit after being idle for [`IDLE_TIMEOUT`].
+/// The pool is shared by the runtimes of all threads.
+struct Pool {
+    state: Mutex<PoolState>,
+    cond: Condvar
+}
+
+struct PoolState {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    idle: usize
+}
+
+static POOL: OnceLock<Pool> = OnceLock::new();
+
+fn pool() -> &'static Pool {
+    POOL.get_or_init(|| Pool {
+        state: Mutex::new(PoolState { jobs: VecDeque::new(), threads: 0, idle: 0 }),
+        cond: Condvar::new()
+    })
+}
+
+impl Pool {
+    fn execute(&'static self, job: Job) {
+        let mut state = self.state.lock().unwrap();
+        state.jobs.push_back(job);
+
+        if state.idle == 0 && state.threads < MAX_THREADS {
+            state.threads += 1;
+
+            thread::Builder::new()
+                .name("uring-blocking".into())
+                .spawn(move || self.work())
+                .expect("Failed to spawn blocking pool thread");
+        }
+        else {
+            self.cond.notify_one();
+        }
+    }
+
+    fn work(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock().unwrap();
+                continue;
+            }
+
+            state.idle += 1;
+            let (guard, timeout) = self.cond.wait_timeout(state, IDLE_TIMEOUT).unwrap();
+            state = guard;
+            state.idle -= 1;
+
+            if timeout.timed_out() && state.jobs.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+struct Shared<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>
+}
+
+/// Future of a function running on the blocking pool
+/// 
+/// Dropping it doesn't stop the function, its result is just discarded.
+/// If the function panics, the panic is resumed in the task awaiting it.
+pub struct BlockingFut<T> {
+    shared: Arc<Mutex<Shared<T>>>
+}
+
+impl<T> Future for BlockingFut<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.lock().unwrap();
+
+        match shared.result.take() {
+            Some(res) => {
+                drop(shared);
+
+                match res {
+                    Ok(res) => Poll::Ready(res),
+                    Err(payload) => panic::resume_unwind(payload)
+                }
+            },
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Runs a blocking function on the blocking pool, so it doesn't block the runtime's thread
+/// 
+/// The task is woken through its waker once the function returns, which goes through
+/// the runtime's remote wakeups since it happens on another thread.
+pub fn run_blocking<T, F>(f: F) -> BlockingFut<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static
+{
+    let shared = Arc::new(Mutex::new(Shared { result: None, waker: None }));
+    let job_shared = shared.clone();
+
+    pool().execute(Box::new(move || {
+        let res = panic::catch_unwind(AssertUnwindSafe(f));
+
+        let waker = {
+            let mut shared = job_shared.lock().unwrap();
+            shared.result = Some(res);
+            shared.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }));
+
+    BlockingFut { shared }
+}
@@ -1,17 +1,28 @@
 use std::fs::File;
-use std::io;
+use std::io::{self, Seek, SeekFrom};
+use std::ffi::OsStr;
+use std::sync::Arc;
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use crate::fs::OpenOptions;
-use super::uring_fut::UringFut;
-use super::libc_result_to_std;
+use crate::buf::{IoBuf, IoBufMut};
+use super::uring_fut::{UringFut, OpensFd};
+use super::{libc_result_to_std, run_blocking, BlockingFut, read_sqe, write_sqe, is_supported, iovecs, iovecs_mut, set_init_vectored};
+use super::handle::{Handle, reserve_slot, destination};
 use io_uring::opcode;
-use io_uring::types::Fd;
+use io_uring::types::{Fd, FsyncFlags, OpenHow};
 use std::ffi::CString;
-use std::os::fd::{AsRawFd, FromRawFd};
-use crate::RUNTIME;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 
-pub async fn file_open(path: &Path, opts: &OpenOptions) -> io::Result<File> {
+/// Converts a path into a C string which can be passed to the kernel
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contained a null byte"))
+}
+
+/// Opens a path relative to `dirfd`
+pub async fn file_open(dirfd: RawFd, path: &Path, opts: &OpenOptions) -> io::Result<Handle<File>> {
     let mut flags = match (opts.read, opts.write) {
         (true, false) => libc::O_RDONLY,
         (false, true) => libc::O_WRONLY,
@@ -35,39 +46,521 @@ pub async fn file_open(path: &Path, opts: &OpenOptions) -> io::Result<File> {
         flags |= libc::O_EXCL;
     }
 
-    let dirfd = Fd(libc::AT_FDCWD);
-    let path = CString::new(path.as_os_str().as_bytes()).expect("Path contained null byte");
+    if opts.tmpfile {
+        // O_TMPFILE creates the file itself, O_CREAT on top of it would apply to the directory
+        if opts.create || opts.create_new {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "tmpfile can't be combined with create or create_new"));
+        }
 
-    let sqe = opcode::OpenAt::new(dirfd, path.as_ptr() as *const _)
-        .flags(flags)
-        .mode(0o666)
-        .build();
+        flags |= libc::O_TMPFILE;
+    }
 
-    let res = UringFut::new(sqe).await;
+    if opts.direct {
+        flags |= libc::O_DIRECT;
+    }
+
+    if opts.cloexec {
+        flags |= libc::O_CLOEXEC;
+    }
 
+    flags |= opts.custom_flags & !libc::O_ACCMODE;
+
+    // The regular fd of files in the registered files table is always close-on-exec
+    let (res, direct) = open_at(dirfd, path, flags, opts.mode, opts.resolve, opts.cloexec).await?;
+
+    Handle::opened(res, direct).await
+}
+
+pub async fn fs_open(dirfd: RawFd, path: &Path, flags: i32, mode: libc::mode_t) -> io::Result<File> {
+    let (res, _) = open_at(dirfd, path, flags, mode, 0, false).await?;
+    libc_result_to_std(res).map(|fd| unsafe { File::from_raw_fd(fd) })
+}
+
+/// Opens a path relative to `dirfd` with `openat2`, restricting how the path is resolved with `RESOLVE_*` flags
+pub async fn fs_open2(dirfd: RawFd, path: &Path, flags: i32, mode: libc::mode_t, resolve: u64) -> io::Result<File> {
+    let (res, _) = open_at(dirfd, path, flags, mode, resolve, false).await?;
     libc_result_to_std(res).map(|fd| unsafe { File::from_raw_fd(fd) })
 }
 
-pub async fn file_read(file: &File, buf: &mut [u8]) -> io::Result<usize> {
-    let sqe = opcode::Read::new(Fd(file.as_raw_fd()), buf.as_mut_ptr(), buf.len() as u32).build();
-    let res = UringFut::new(sqe).await;
+/// Opens a path, using `openat2` if `resolve` is set
+/// 
+/// Returns the result of the operation, along with whether the file was opened into the registered
+/// files table, which is only attempted if `allow_direct` is set. Both must be passed to [`Handle::opened()`].
+async fn open_at(
+    dirfd: RawFd,
+    path: &Path,
+    flags: i32,
+    mode: libc::mode_t,
+    resolve: u64,
+    allow_direct: bool
+) -> io::Result<(i32, bool)> {
+    let path = path_to_cstring(path)?;
 
-    libc_result_to_std(res).map(|bytes| bytes as usize)
+    if resolve != 0 && !is_supported(opcode::OpenAt2::CODE) {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "openat2 isn't supported by the kernel"));
+    }
+
+    let direct = allow_direct && reserve_slot();
+
+    // The kernel rejects O_CLOEXEC on files opened into the registered files table, which have no fd yet
+    let flags = if direct { flags & !libc::O_CLOEXEC } else { flags };
+
+    if resolve == 0 {
+        let sqe = opcode::OpenAt::new(Fd(dirfd), path.as_ptr() as *const _)
+            .flags(flags)
+            .mode(mode)
+            .file_index(destination(direct))
+            .build();
+
+        let (res, op) = UringFut::with_data(sqe, OpensFd::new(direct, Box::new(path))).await;
+        return Ok((res, op.into_parts().0));
+    }
+
+    // The kernel rejects a mode unless a file may be created
+    let mode = if flags & (libc::O_CREAT | libc::O_TMPFILE) != 0 { mode } else { 0 };
+
+    let how = Box::new(OpenHow::new()
+        .flags(flags as u64)
+        .mode(mode as u64)
+        .resolve(resolve));
+
+    let sqe = opcode::OpenAt2::new(Fd(dirfd), path.as_ptr() as *const _, &*how)
+        .file_index(destination(direct))
+        .build();
+
+    let (res, op) = UringFut::with_data(sqe, OpensFd::new(direct, Box::new((path, how)))).await;
+    Ok((res, op.into_parts().0))
 }
 
-pub async fn file_write(file: &File, buf: &[u8]) -> io::Result<usize> {
-    let sqe = opcode::Write::new(Fd(file.as_raw_fd()), buf.as_ptr(), buf.len() as u32).build();
-    let res = UringFut::new(sqe).await;
+/// Opens a directory relative to `dirfd`, restricting how the path is resolved if `resolve` is set
+pub async fn dir_open(dirfd: RawFd, path: &Path, resolve: u64) -> io::Result<OwnedFd> {
+    let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
 
-    libc_result_to_std(res).map(|bytes| bytes as usize)
+    let dir = match resolve {
+        0 => fs_open(dirfd, path, flags, 0).await?,
+        _ => fs_open2(dirfd, path, flags, 0, resolve).await?
+    };
+
+    Ok(dir.into())
 }
 
-pub fn file_close(file: &File) {
-    RUNTIME.with_borrow_mut(|rt| {
-        let sqe = opcode::Close::new(Fd(file.as_raw_fd()))
-            .build()
-            .user_data(0); // IoKey 0 reserved for fd closes
+/// Reads a batch of directory entries into the buffer, returning the number of bytes read,
+/// which is 0 once the end of the directory is reached
+/// 
+/// There is no io_uring equivalent of `getdents64`, so it runs on the blocking pool.
+pub fn dir_read(dir: &Arc<OwnedFd>, mut buf: Vec<u8>) -> BlockingFut<(io::Result<usize>, Vec<u8>)> {
+    let dir = dir.clone();
+
+    run_blocking(move || {
+        let res = unsafe {
+            libc::syscall(libc::SYS_getdents64, dir.as_raw_fd(), buf.as_mut_ptr(), buf.capacity())
+        };
+
+        let res = if res < 0 {
+            Err(io::Error::last_os_error())
+        }
+        else {
+            unsafe { buf.set_len(res as usize) };
+            Ok(res as usize)
+        };
+
+        (res, buf)
+    })
+}
+
+/// A directory entry as returned by `getdents64`
+pub struct RawDirEntry<'a> {
+    pub ino: u64,
+    pub d_type: u8,
+    pub name: &'a OsStr
+}
+
+/// Parses the `linux_dirent64` entries read by [`dir_read()`]
+pub fn parse_dirents(mut buf: &[u8]) -> impl Iterator<Item = RawDirEntry<'_>> {
+    // Offsets of the fields inside of linux_dirent64
+    const INO: usize = 0;
+    const RECLEN: usize = 16;
+    const TYPE: usize = 18;
+    const NAME: usize = 19;
+
+    std::iter::from_fn(move || {
+        if buf.len() < NAME {
+            return None;
+        }
+
+        let ino = u64::from_ne_bytes(buf[INO..INO + 8].try_into().unwrap());
+        let reclen = u16::from_ne_bytes(buf[RECLEN..RECLEN + 2].try_into().unwrap()) as usize;
+        let d_type = buf[TYPE];
+
+        // The name is null terminated and padded up to the record's length
+        let name = &buf[NAME..reclen];
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        let name = OsStr::from_bytes(&name[..name_len]);
+
+        buf = &buf[reclen..];
+
+        Some(RawDirEntry { ino, d_type, name })
+    })
+}
+
+/// Offset which makes reads and writes use and advance the file position,
+/// used for files which can't be read from or written to at an offset
+pub const CURRENT_POS: u64 = u64::MAX; // -1
+
+pub async fn file_read<B: IoBufMut>(file: &Handle<File>, mut buf: B, offset: u64) -> (io::Result<usize>, B) {
+    let (fd, flags) = file.target();
+    let sqe = read_sqe(fd, buf.fixed_index(), buf.stable_mut_ptr(), buf.bytes_total() as u32, offset).flags(flags);
 
-        rt.plat.submit_sqe(sqe);   
+    let (res, mut buf) = UringFut::with_data(sqe, buf).await;
+
+    let res = libc_result_to_std(res).map(|bytes| {
+        unsafe { buf.set_init(bytes as usize) };
+        bytes as usize
+    });
+
+    (res, buf)
+}
+
+pub async fn file_write<B: IoBuf>(file: &Handle<File>, buf: B, offset: u64) -> (io::Result<usize>, B) {
+    let (fd, flags) = file.target();
+    let sqe = write_sqe(fd, buf.fixed_index(), buf.stable_ptr(), buf.bytes_init() as u32, offset).flags(flags);
+
+    let (res, buf) = UringFut::with_data(sqe, buf).await;
+
+    (libc_result_to_std(res).map(|bytes| bytes as usize), buf)
+}
+
+pub async fn file_read_vectored<B: IoBufMut>(file: &Handle<File>, mut bufs: Vec<B>, offset: u64) -> (io::Result<usize>, Vec<B>) {
+    let iovecs = iovecs_mut(&mut bufs);
+    let (fd, flags) = file.target();
+
+    let sqe = opcode::Readv::new(fd, iovecs.as_ptr(), iovecs.len() as u32)
+        .offset(offset)
+        .build()
+        .flags(flags);
+
+    let (res, (mut bufs, _)) = UringFut::with_data(sqe, (bufs, iovecs)).await;
+
+    let res = libc_result_to_std(res).map(|bytes| {
+        set_init_vectored(&mut bufs, bytes as usize);
+        bytes as usize
     });
+
+    (res, bufs)
+}
+
+pub async fn file_write_vectored<B: IoBuf>(file: &Handle<File>, bufs: Vec<B>, offset: u64) -> (io::Result<usize>, Vec<B>) {
+    let iovecs = iovecs(&bufs);
+    let (fd, flags) = file.target();
+
+    let sqe = opcode::Writev::new(fd, iovecs.as_ptr(), iovecs.len() as u32)
+        .offset(offset)
+        .build()
+        .flags(flags);
+
+    let (res, (bufs, _)) = UringFut::with_data(sqe, (bufs, iovecs)).await;
+
+    (libc_result_to_std(res).map(|bytes| bytes as usize), bufs)
+}
+
+pub async fn file_sync(file: &Handle<File>, data_only: bool) -> io::Result<()> {
+    let sync_flags = if data_only { FsyncFlags::DATASYNC } else { FsyncFlags::empty() };
+    let (fd, flags) = file.target();
+
+    let sqe = opcode::Fsync::new(fd).flags(sync_flags).build().flags(flags);
+    let (res, _) = UringFut::new(sqe).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn file_sync_range(file: &Handle<File>, offset: u64, len: u32, sync_flags: u32) -> io::Result<()> {
+    let (fd, flags) = file.target();
+
+    let sqe = opcode::SyncFileRange::new(fd, len)
+        .offset(offset)
+        .flags(sync_flags)
+        .build()
+        .flags(flags);
+
+    let (res, _) = UringFut::new(sqe).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn file_fallocate(file: &Handle<File>, offset: u64, len: u64, mode: i32) -> io::Result<()> {
+    let (fd, flags) = file.target();
+
+    let sqe = opcode::Fallocate::new(fd, len)
+        .offset(offset)
+        .mode(mode)
+        .build()
+        .flags(flags);
+
+    let (res, _) = UringFut::new(sqe).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn file_truncate(file: &Handle<File>, len: u64) -> io::Result<()> {
+    // Only supported since 6.9
+    if !is_supported(opcode::Ftruncate::CODE) {
+        // The fd is duplicated so that it stays valid even if the file is closed while truncating
+        let file = file.try_clone()?;
+        return run_blocking(move || file.set_len(len)).await;
+    }
+
+    let (fd, flags) = file.target();
+    let sqe = opcode::Ftruncate::new(fd, len).build().flags(flags);
+    let (res, _) = UringFut::new(sqe).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+/// Largest number of bytes Linux transfers in a single read or write
+const MAX_RW_COUNT: usize = 0x7ffff000;
+
+/// Reads into the vector's spare capacity, appending the bytes read to it
+pub async fn file_read_append(file: &Handle<File>, mut buf: Vec<u8>, offset: u64) -> (io::Result<usize>, Vec<u8>) {
+    let len = buf.len();
+    let spare = (buf.capacity() - len).min(MAX_RW_COUNT);
+    let (fd, flags) = file.target();
+
+    let sqe = read_sqe(fd, None, unsafe { buf.as_mut_ptr().add(len) }, spare as u32, offset).flags(flags);
+
+    let (res, mut buf) = UringFut::with_data(sqe, buf).await;
+
+    let res = libc_result_to_std(res).map(|bytes| {
+        unsafe { buf.set_len(len + bytes as usize) };
+        bytes as usize
+    });
+
+    (res, buf)
+}
+
+/// Writes the buffer's initialized bytes starting from `start`, used to continue after short writes
+pub async fn file_write_from<B: IoBuf>(file: &Handle<File>, buf: B, start: usize, offset: u64) -> (io::Result<usize>, B) {
+    let len = (buf.bytes_init() - start).min(MAX_RW_COUNT);
+    let (fd, flags) = file.target();
+
+    let sqe = write_sqe(fd, buf.fixed_index(), unsafe { buf.stable_ptr().add(start) }, len as u32, offset).flags(flags);
+
+    let (res, buf) = UringFut::with_data(sqe, buf).await;
+
+    (libc_result_to_std(res).map(|bytes| bytes as usize), buf)
+}
+
+/// Number of bytes spliced in and out of the pipe at once, the pipe is resized to fit it if possible
+const SPLICE_CHUNK: usize = 1 << 20;
+
+/// Copies `from` into `to` starting at their current offsets, returning the number of bytes copied
+/// 
+/// The data is spliced through a pipe, so it never gets copied to userspace.
+/// Offsets are `None` for files which aren't seekable, which then use their file position.
+pub async fn file_copy(from: &File, from_offset: Option<u64>, to: &File, to_offset: Option<u64>) -> io::Result<u64> {
+    if !is_supported(opcode::Splice::CODE) {
+        let (mut from, mut to) = (from.try_clone()?, to.try_clone()?);
+
+        if let Some(offset) = from_offset {
+            from.seek(SeekFrom::Start(offset))?;
+        }
+
+        if let Some(offset) = to_offset {
+            to.seek(SeekFrom::Start(offset))?;
+        }
+
+        return run_blocking(move || io::copy(&mut from, &mut to)).await;
+    }
+
+    // The operations own the fds, so that they can't be reused by another file while a splice is in flight
+    let (from, to) = (Arc::new(OwnedFd::from(from.try_clone()?)), Arc::new(OwnedFd::from(to.try_clone()?)));
+    let (pipe_read, pipe_write) = pipe()?;
+    let (pipe_read, pipe_write) = (Arc::new(pipe_read), Arc::new(pipe_write));
+
+    // Splicing fewer bytes at a time only means more round trips, so it's fine if this fails
+    unsafe { libc::fcntl(pipe_write.as_raw_fd(), libc::F_SETPIPE_SZ, SPLICE_CHUNK as libc::c_int) };
+
+    // -1 makes splice use the file position
+    let mut from_offset = from_offset.map_or(-1, |offset| offset as i64);
+    let mut to_offset = to_offset.map_or(-1, |offset| offset as i64);
+    let mut copied = 0;
+
+    loop {
+        let filled = splice(&from, from_offset, &pipe_write, -1, SPLICE_CHUNK).await?;
+
+        if filled == 0 {
+            return Ok(copied);
+        }
+
+        // The pipe has to be fully drained before it's filled again
+        let mut left = filled;
+
+        while left > 0 {
+            let drained = splice(&pipe_read, -1, &to, to_offset, left).await?;
+
+            if drained == 0 {
+                return Err(io::Error::from(io::ErrorKind::WriteZero));
+            }
+
+            left -= drained;
+
+            if to_offset != -1 {
+                to_offset += drained as i64;
+            }
+        }
+
+        if from_offset != -1 {
+            from_offset += filled as i64;
+        }
+
+        copied += filled as u64;
+    }
+}
+
+async fn splice(fd_in: &Arc<OwnedFd>, off_in: i64, fd_out: &Arc<OwnedFd>, off_out: i64, len: usize) -> io::Result<usize> {
+    let sqe = opcode::Splice::new(Fd(fd_in.as_raw_fd()), off_in, Fd(fd_out.as_raw_fd()), off_out, len as u32)
+        .flags(libc::SPLICE_F_MOVE)
+        .build();
+
+    let (res, _) = UringFut::with_data(sqe, (fd_in.clone(), fd_out.clone())).await;
+
+    libc_result_to_std(res).map(|bytes| bytes as usize)
+}
+
+/// Creates a pipe, returning its read end followed by its write end
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    cvt(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) })?;
+
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Checks whether the file supports reading and writing at an offset, pipes and sockets don't
+pub fn file_seekable(file: &File) -> bool {
+    // This doesn't block, so there's no need to go through the ring
+    unsafe { libc::lseek(file.as_raw_fd(), 0, libc::SEEK_CUR) >= 0 }
+}
+
+/// Checks whether the file was opened in append mode, writes then always go to the end of the file
+pub fn file_appends(file: &File) -> bool {
+    unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) & libc::O_APPEND != 0 }
+}
+
+/// Converts the result of a libc call which returns -1 and sets errno on failure
+fn cvt(res: libc::c_int) -> io::Result<()> {
+    if res < 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+}
+
+/// Gets the fd paths are resolved relative to, `None` being the current working directory
+fn dir_fd(dir: &Option<Arc<OwnedFd>>) -> RawFd {
+    dir.as_ref().map_or(libc::AT_FDCWD, |dir| dir.as_raw_fd())
+}
+
+// The directories are owned by the operations so that their fds can't be reused by another file
+// while the operation is queued or running, which would make it act on that file instead
+
+pub async fn fs_rename(from_dir: Option<Arc<OwnedFd>>, from: &Path, to_dir: Option<Arc<OwnedFd>>, to: &Path) -> io::Result<()> {
+    let paths = (path_to_cstring(from)?, path_to_cstring(to)?);
+
+    if !is_supported(opcode::RenameAt::CODE) {
+        return run_blocking(move || {
+            cvt(unsafe { libc::renameat(dir_fd(&from_dir), paths.0.as_ptr(), dir_fd(&to_dir), paths.1.as_ptr()) })
+        }).await;
+    }
+
+    let sqe = opcode::RenameAt::new(Fd(dir_fd(&from_dir)), paths.0.as_ptr(), Fd(dir_fd(&to_dir)), paths.1.as_ptr()).build();
+    let (res, _) = UringFut::with_data(sqe, (paths, from_dir, to_dir)).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn fs_unlink(dir: Option<Arc<OwnedFd>>, path: &Path, remove_dir: bool) -> io::Result<()> {
+    let path = path_to_cstring(path)?;
+    let flags = if remove_dir { libc::AT_REMOVEDIR } else { 0 };
+
+    if !is_supported(opcode::UnlinkAt::CODE) {
+        return run_blocking(move || {
+            cvt(unsafe { libc::unlinkat(dir_fd(&dir), path.as_ptr(), flags) })
+        }).await;
+    }
+
+    let sqe = opcode::UnlinkAt::new(Fd(dir_fd(&dir)), path.as_ptr())
+        .flags(flags)
+        .build();
+
+    let (res, _) = UringFut::with_data(sqe, (path, dir)).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn fs_mkdir(dir: Option<Arc<OwnedFd>>, path: &Path, mode: libc::mode_t) -> io::Result<()> {
+    let path = path_to_cstring(path)?;
+
+    if !is_supported(opcode::MkDirAt::CODE) {
+        return run_blocking(move || {
+            cvt(unsafe { libc::mkdirat(dir_fd(&dir), path.as_ptr(), mode) })
+        }).await;
+    }
+
+    let sqe = opcode::MkDirAt::new(Fd(dir_fd(&dir)), path.as_ptr())
+        .mode(mode)
+        .build();
+
+    let (res, _) = UringFut::with_data(sqe, (path, dir)).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn fs_hard_link(original: &Path, link: &Path) -> io::Result<()> {
+    if !is_supported(opcode::LinkAt::CODE) {
+        let (original, link) = (original.to_owned(), link.to_owned());
+        return run_blocking(move || std::fs::hard_link(original, link)).await;
+    }
+
+    let paths = (path_to_cstring(original)?, path_to_cstring(link)?);
+    let dirfd = Fd(libc::AT_FDCWD);
+
+    let sqe = opcode::LinkAt::new(dirfd, paths.0.as_ptr(), dirfd, paths.1.as_ptr()).build();
+    let (res, _) = UringFut::with_data(sqe, paths).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn fs_symlink(original: &Path, link: &Path) -> io::Result<()> {
+    if !is_supported(opcode::SymlinkAt::CODE) {
+        let (original, link) = (original.to_owned(), link.to_owned());
+        return run_blocking(move || std::os::unix::fs::symlink(original, link)).await;
+    }
+
+    let paths = (path_to_cstring(original)?, path_to_cstring(link)?);
+
+    let sqe = opcode::SymlinkAt::new(Fd(libc::AT_FDCWD), paths.0.as_ptr(), paths.1.as_ptr()).build();
+    let (res, _) = UringFut::with_data(sqe, paths).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+const STATX_MASK: u32 = libc::STATX_BASIC_STATS | libc::STATX_BTIME | libc::STATX_MNT_ID;
+
+async fn statx(dirfd: Fd, path: CString, flags: i32) -> io::Result<libc::statx> {
+    let mut stat: Box<MaybeUninit<libc::statx>> = Box::new(MaybeUninit::uninit());
+
+    let sqe = opcode::Statx::new(dirfd, path.as_ptr(), stat.as_mut_ptr() as *mut _)
+        .flags(flags | libc::AT_STATX_SYNC_AS_STAT)
+        .mask(STATX_MASK)
+        .build();
+
+    let (res, (_, stat)) = UringFut::with_data(sqe, (path, stat)).await;
+
+    libc_result_to_std(res).map(|_| unsafe { stat.assume_init_read() })
+}
+
+/// Gets the metadata of the file at a path relative to `dirfd`, following symlinks if `follow` is set
+pub async fn fs_statx(dirfd: RawFd, path: &Path, follow: bool) -> io::Result<libc::statx> {
+    let flags = if follow { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+    statx(Fd(dirfd), path_to_cstring(path)?, flags).await
+}
+
+pub async fn file_statx(file: &File) -> io::Result<libc::statx> {
+    // An empty path with AT_EMPTY_PATH makes statx operate on the dirfd itself
+    statx(Fd(file.as_raw_fd()), CString::default(), libc::AT_EMPTY_PATH).await
 }
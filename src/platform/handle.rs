@@ -0,0 +1,148 @@
+use std::io;
+use std::mem::{self, ManuallyDrop};
+use std::ops::Deref;
+use std::os::fd::{AsRawFd, FromRawFd};
+use io_uring::{opcode, squeue};
+use io_uring::types::{Fd, Fixed, DestinationSlot};
+use crate::RUNTIME;
+
+use super::libc_result_to_std;
+use super::uring_fut::UringFut;
+
+/// A slot of the registered files table, along with the ring it belongs to
+#[derive(Clone, Copy)]
+struct Slot {
+    index: u32,
+    ring: u64
+}
+
+/// A file or socket owned by the runtime, which closes it once dropped
+/// 
+/// It may also have a slot in the registered files table, in which case operations target the slot
+/// instead of the fd. The fd is still there for everything that isn't done through the ring.
+pub struct Handle<T: AsRawFd> {
+    inner: ManuallyDrop<T>,
+    slot: Option<Slot>
+}
+
+impl<T: AsRawFd> Handle<T> {
+    /// Wraps a file or socket which isn't in the registered files table
+    pub fn new(inner: T) -> Self {
+        Self { inner: ManuallyDrop::new(inner), slot: None }
+    }
+
+    /// The fd to put into an sqe, along with the flags to set on it
+    pub fn target(&self) -> (Fd, squeue::Flags) {
+        match self.slot {
+            Some(slot) if slot.ring == current_ring() => (Fd(slot.index as i32), squeue::Flags::FIXED_FILE),
+            _ => (Fd(self.inner.as_raw_fd()), squeue::Flags::empty())
+        }
+    }
+}
+
+impl<T: AsRawFd + FromRawFd> Handle<T> {
+    /// Completes an operation which opens a file, `direct` is the return value of [`reserve_slot()`]
+    /// and the operation's file index must have been set to [`DestinationSlot::auto_target()`] if it is
+    /// 
+    /// Files opened into the registered files table are also given a regular fd.
+    pub async fn opened(res: i32, direct: bool) -> io::Result<Self> {
+        if !direct {
+            return libc_result_to_std(res).map(|fd| Self::new(unsafe { T::from_raw_fd(fd) }));
+        }
+
+        let index = match libc_result_to_std(res) {
+            Ok(index) => index as u32,
+            Err(err) => {
+                RUNTIME.with_borrow_mut(|rt| rt.plat.release_file_slot());
+                return Err(err);
+            }
+        };
+
+        // Makes sure the slot is closed if installing the fd fails or is cancelled
+        let guard = SlotGuard(index);
+
+        let sqe = opcode::FixedFdInstall::new(Fixed(index), 0).build();
+        let (res, _) = UringFut::new(sqe).await;
+        let fd = libc_result_to_std(res)?;
+
+        mem::forget(guard);
+
+        Ok(Self {
+            inner: ManuallyDrop::new(unsafe { T::from_raw_fd(fd) }),
+            slot: Some(Slot { index, ring: current_ring() })
+        })
+    }
+}
+
+impl<T: AsRawFd> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: AsRawFd> Drop for Handle<T> {
+    fn drop(&mut self) {
+        close(Fd(self.inner.as_raw_fd()));
+
+        if let Some(slot) = self.slot.filter(|slot| slot.ring == current_ring()) {
+            close_slot(slot.index);
+        }
+    }
+}
+
+/// Reserves a slot in the registered files table for a file about to be opened, returning whether the file
+/// should be opened into the table
+/// 
+/// The reservation must be handed to the operation's [`OpensFd`](super::uring_fut::OpensFd) before anything is awaited,
+/// and taken back to be passed to [`Handle::opened()`], which releases it if the file failed to open.
+pub fn reserve_slot() -> bool {
+    RUNTIME.with_borrow_mut(|rt| rt.plat.reserve_file_slot())
+}
+
+/// Checks whether a multishot operation should open files into the registered files table, which it
+/// does without reserving slots since it may open any number of them
+/// 
+/// The slot of each file it opens must be counted with [`take_slot()`] before passing it to [`Handle::opened()`].
+pub fn multishot_direct() -> bool {
+    RUNTIME.with_borrow(|rt| rt.plat.file_slots_available())
+}
+
+/// Counts the slot the kernel picked for a file opened by a multishot operation
+pub fn take_slot() {
+    RUNTIME.with_borrow_mut(|rt| rt.plat.take_file_slot());
+}
+
+/// The file index to set on operations opening a file
+pub fn destination(direct: bool) -> Option<DestinationSlot> {
+    direct.then(DestinationSlot::auto_target)
+}
+
+/// Closes a slot of the registered files table when dropped
+struct SlotGuard(u32);
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        close_slot(self.0);
+    }
+}
+
+fn current_ring() -> u64 {
+    RUNTIME.with_borrow(|rt| rt.plat.id)
+}
+
+fn close_slot(index: u32) {
+    let sqe = opcode::Close::new(Fixed(index)).build();
+
+    RUNTIME.with_borrow_mut(|rt| {
+        rt.plat.submit_sqe(sqe.user_data(0)); // IoKey 0 reserved for closes
+        rt.plat.release_file_slot();
+    });
+}
+
+fn close(fd: Fd) {
+    let sqe = opcode::Close::new(fd).build();
+
+    RUNTIME.with_borrow_mut(|rt| rt.plat.submit_sqe(sqe.user_data(0)));
+}
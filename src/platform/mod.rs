@@ -6,22 +6,37 @@ mod socket;
 mod uring_fut;
 #[cfg(target_os = "linux")]
 mod platform;
+#[cfg(target_os = "linux")]
+mod remote;
+#[cfg(target_os = "linux")]
+mod blocking;
+#[cfg(target_os = "linux")]
+mod handle;
 
 use std::time::Duration;
 use std::mem;
 use std::net::{SocketAddr, Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
 use std::io;
+use crate::buf::{IoBuf, IoBufMut};
 
 #[cfg(target_os = "linux")]
-use io_uring::{opcode, types::Timespec};
+use io_uring::{opcode, squeue, types::{Fd, Timespec}};
+#[cfg(target_os = "linux")]
+use crate::RUNTIME;
 #[cfg(target_os = "linux")]
 pub use platform::*;
 #[cfg(target_os = "linux")]
-pub (crate) use  uring_fut::UringFut;
+pub (crate) use  uring_fut::{UringFut, UringStream, OpensFd};
 #[cfg(target_os = "linux")]
 pub (crate) use file::*;
 #[cfg(target_os = "linux")]
 pub (crate) use socket::*;
+#[cfg(target_os = "linux")]
+pub (crate) use remote::RemoteWakeups;
+#[cfg(target_os = "linux")]
+pub (crate) use blocking::{run_blocking, BlockingFut};
+#[cfg(target_os = "linux")]
+pub (crate) use handle::{Handle, multishot_direct, take_slot};
 
 type IoKey = u32;
 
@@ -29,10 +44,59 @@ const MAX_LIBC_SOCKADDR_SIZE: usize = mem::size_of::<libc::sockaddr_in6>();
 
 
 pub async fn sleep(dur: Duration) {
-    let timespec = Timespec::from(dur);
-    let sqe = opcode::Timeout::new(&timespec).build();
+    // The kernel only reads the timespec once the sqe is submitted, so
+    // it's boxed to be kept alive if we're dropped before that
+    let timespec = Box::new(Timespec::from(dur));
+    let sqe = opcode::Timeout::new(&*timespec).build();
+
+    UringFut::with_data(sqe, timespec).await;
+}
+
+/// Checks whether the kernel supports an opcode, ops which aren't supported fall back
+/// to their blocking std equivalents, running on the blocking pool
+fn is_supported(code: u8) -> bool {
+    RUNTIME.with_borrow(|rt| rt.plat.is_supported(code))
+}
 
-    UringFut::new(sqe).await;
+/// Builds a read into `len` bytes at `ptr`, using `ReadFixed` if they're part of
+/// the registered buffer at `fixed_index`
+fn read_sqe(fd: Fd, fixed_index: Option<u16>, ptr: *mut u8, len: u32, offset: u64) -> squeue::Entry {
+    match fixed_index {
+        Some(index) => opcode::ReadFixed::new(fd, ptr, len, index).offset(offset).build(),
+        None => opcode::Read::new(fd, ptr, len).offset(offset).build()
+    }
+}
+
+/// Builds a write of `len` bytes at `ptr`, using `WriteFixed` if they're part of
+/// the registered buffer at `fixed_index`
+fn write_sqe(fd: Fd, fixed_index: Option<u16>, ptr: *const u8, len: u32, offset: u64) -> squeue::Entry {
+    match fixed_index {
+        Some(index) => opcode::WriteFixed::new(fd, ptr, len, index).offset(offset).build(),
+        None => opcode::Write::new(fd, ptr, len).offset(offset).build()
+    }
+}
+
+/// Builds iovecs over the initialized bytes of each buffer, for vectored writes
+fn iovecs<B: IoBuf>(bufs: &[B]) -> Vec<libc::iovec> {
+    bufs.iter()
+        .map(|buf| libc::iovec { iov_base: buf.stable_ptr() as *mut _, iov_len: buf.bytes_init() })
+        .collect()
+}
+
+/// Builds iovecs over the whole of each buffer, for vectored reads
+fn iovecs_mut<B: IoBufMut>(bufs: &mut [B]) -> Vec<libc::iovec> {
+    bufs.iter_mut()
+        .map(|buf| libc::iovec { iov_base: buf.stable_mut_ptr() as *mut _, iov_len: buf.bytes_total() })
+        .collect()
+}
+
+/// Marks the bytes of a vectored read as initialized, the kernel fills the buffers in order
+fn set_init_vectored<B: IoBufMut>(bufs: &mut [B], mut bytes: usize) {
+    for buf in bufs {
+        let filled = bytes.min(buf.bytes_total());
+        unsafe { buf.set_init(filled) };
+        bytes -= filled;
+    }
 }
 
 fn libc_addr_to_std(addr: &libc::sockaddr) -> SocketAddr {
@@ -1,12 +1,82 @@
-use io_uring::{IoUring, opcode, squeue};
-use crate::runtime::TaskId;
+use std::any::Any;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::Waker;
+use std::os::fd::AsRawFd;
+use std::collections::VecDeque;
+use io_uring::{IoUring, opcode, squeue, cqueue};
+use io_uring::types::Fd;
 use crate::error::UringError;
+use crate::runtime::TaskId;
 use nohash::IntMap;
-use super::IoKey;
+use super::{IoKey, RemoteWakeups};
+use super::uring_fut::discard_cqe;
 use io_uring::Probe;
 
-fn new_io_uring() -> Result<IoUring, UringError> {
-    let ring = IoUring::new(128).map_err(|err| UringError::FailedInit(err))?;
+/// IoKey reserved for the read of the remote wakeups eventfd
+const REMOTE_WAKE_KEY: IoKey = IoKey::MAX;
+
+/// Configuration of the io_uring instance, set through [`Builder`](crate::Builder)
+#[derive(Clone, Debug)]
+pub struct RingConfig {
+    pub entries: u32,
+    pub cq_entries: Option<u32>,
+    pub sqpoll_idle: Option<u32>,
+    pub sqpoll_cpu: Option<u32>,
+    pub coop_taskrun: bool,
+    pub single_issuer: bool,
+    pub defer_taskrun: bool,
+    pub registered_files: Option<u32>
+}
+
+impl Default for RingConfig {
+    fn default() -> Self {
+        Self {
+            entries: 128,
+            cq_entries: None,
+            sqpoll_idle: None,
+            sqpoll_cpu: None,
+            coop_taskrun: false,
+            single_issuer: false,
+            defer_taskrun: false,
+            registered_files: None
+        }
+    }
+}
+
+/// Creates the io_uring, checking that the kernel supports everything we require
+/// 
+/// Also returns the probe of supported opcodes, so that optional opcodes
+/// can fall back to blocking implementations when not supported
+fn new_io_uring(config: &RingConfig) -> Result<(IoUring, Probe), UringError> {
+    let mut builder = IoUring::builder();
+
+    if let Some(cq_entries) = config.cq_entries {
+        builder.setup_cqsize(cq_entries);
+    }
+
+    if let Some(idle) = config.sqpoll_idle {
+        builder.setup_sqpoll(idle);
+
+        if let Some(cpu) = config.sqpoll_cpu {
+            builder.setup_sqpoll_cpu(cpu);
+        }
+    }
+
+    if config.coop_taskrun {
+        builder.setup_coop_taskrun();
+    }
+
+    if config.single_issuer {
+        builder.setup_single_issuer();
+    }
+
+    if config.defer_taskrun {
+        builder.setup_defer_taskrun();
+    }
+
+    let ring = builder.build(config.entries).map_err(|err| UringError::FailedInit(err))?;
 
     if !ring.params().is_feature_nodrop() {
         return Err(UringError::UnsupportedFeature("no_drop"));
@@ -29,8 +99,12 @@ fn new_io_uring() -> Result<IoUring, UringError> {
         ("SendMsg", opcode::SendMsg::CODE),
         ("Shutdown", opcode::Shutdown::CODE),
         ("OpenAt", opcode::OpenAt::CODE),
+        ("Statx", opcode::Statx::CODE),
         ("Read", opcode::Read::CODE),
         ("Write", opcode::Write::CODE),
+        ("Fsync", opcode::Fsync::CODE),
+        ("SyncFileRange", opcode::SyncFileRange::CODE),
+        ("Fallocate", opcode::Fallocate::CODE),
         ("Close", opcode::Close::CODE)
     ];
 
@@ -40,57 +114,292 @@ fn new_io_uring() -> Result<IoUring, UringError> {
         }
     }
 
-    Ok(ring)
+    if let Some(count) = config.registered_files {
+        // Files opened into the table are also given a regular fd
+        if !probe.is_supported(opcode::FixedFdInstall::CODE) {
+            return Err(UringError::UnsupportedOpcode("FixedFdInstall"));
+        }
+
+        ring.submitter()
+            .register_files_sparse(count)
+            .map_err(UringError::FailedInit)?;
+    }
+
+    Ok((ring, probe))
+}
+
+
+/// A completion of an operation
+/// 
+/// Most operations produce a single completion, multishot operations and zero-copy sends produce several
+/// and flag all but the last with `IORING_CQE_F_MORE`.
+#[derive(Clone, Copy, Debug)]
+pub struct Cqe {
+    pub result: i32,
+    pub flags: u32
+}
+
+impl Cqe {
+    /// Whether the operation will produce more completions
+    pub fn more(&self) -> bool {
+        cqueue::more(self.flags)
+    }
+
+    /// Whether this is the notification of a zero-copy send, which arrives once the kernel
+    /// no longer uses the buffer
+    pub fn notif(&self) -> bool {
+        cqueue::notif(self.flags)
+    }
+
+    /// The provided buffer the kernel picked for the operation
+    pub fn buffer(&self) -> Option<u16> {
+        cqueue::buffer_select(self.flags)
+    }
 }
 
+/// Counter giving each ring a unique ID, see [`Platform::id`]
+static RING_ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Usage of the registered files table
+struct FileSlots {
+    count: u32,
+    used: u32
+}
 
 pub struct Platform {
     ring: IoUring,
+
+    /// Identifies the ring, since what's registered with a ring is only valid on that ring
+    pub (crate) id: u64,
+    probe: Probe,
     io_key_counter: IoKey,
 
-    pub (crate) submissions: IntMap<IoKey, TaskId>,
-    pub (crate) completions: IntMap<IoKey, i32>,
+    pub (crate) submissions: IntMap<IoKey, Waker>,
+    pub (crate) completions: IntMap<IoKey, Cqe>,
+
+    /// Completions of multishot operations, which stay in `submissions` until their last completion
+    pub (crate) multishot: IntMap<IoKey, VecDeque<Cqe>>,
+
+    /// Data of operations whose futures were dropped before they completed,
+    /// kept alive until their completion arrives
+    pub (crate) orphans: IntMap<IoKey, Box<dyn Any>>,
+
+    pub (crate) remote: Arc<RemoteWakeups>,
+    remote_buf: Box<u64>,
+
+    /// Start of the memory of the registered buffers, identifying the pool which registered them
+    registered_buffers: Option<*const u8>,
+
+    file_slots: Option<FileSlots>,
+
+    /// Group IDs of unregistered buffer rings, reused before new ones are handed out
+    free_buf_groups: Vec<u16>,
+    next_buf_group: u16
 }
 
 impl Platform {
-    pub fn new() -> Result<Self, UringError> {
-        Ok(Self {
-            ring: new_io_uring()?,
+    pub fn new(config: &RingConfig) -> Result<Self, UringError> {
+        let remote = RemoteWakeups::new().map_err(UringError::FailedInit)?;
+
+        let (ring, probe) = new_io_uring(config)?;
+
+        let mut plat = Self {
+            ring,
+            id: RING_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+            probe,
             io_key_counter: 1, // 0 is reserved for the close operations
             submissions: IntMap::default(),
-            completions: IntMap::default()
-        })
+            completions: IntMap::default(),
+            multishot: IntMap::default(),
+            orphans: IntMap::default(),
+            remote: Arc::new(remote),
+            remote_buf: Box::new(0),
+            registered_buffers: None,
+            file_slots: config.registered_files.map(|count| FileSlots { count, used: 0 }),
+            free_buf_groups: Vec::new(),
+            next_buf_group: 0
+        };
+
+        plat.arm_remote_wake();
+        Ok(plat)
     }
 
-    pub fn wait_for_io(&mut self, wakeups: &mut Vec<TaskId>) {
+    /// Waits for IO to complete, placing the wakers of the completed operations into `wakeups`,
+    /// and the IDs of tasks woken up from other threads into `remote_wakeups`
+    pub fn wait_for_io(&mut self, wakeups: &mut Vec<Waker>, remote_wakeups: &mut Vec<TaskId>) {
         self.ring
             .submit_and_wait(1)
             .expect("Failed to submit io_uring");
 
+        let mut rearm = false;
 
-        for cqe in self.ring.completion() {
-            let key = IoKey::from(cqe.user_data() as u32);
+        // Collected first since discarding completions may submit closes
+        let cqes: Vec<_> = self.ring.completion()
+            .map(|cqe| (IoKey::from(cqe.user_data() as u32), Cqe { result: cqe.result(), flags: cqe.flags() }))
+            .collect();
 
-            if let Some(task_id) = self.submissions.remove(&key) {
-                self.completions.insert(key, cqe.result());
-                wakeups.push(task_id);
+        for (key, cqe) in cqes {
+            let more = cqe.more();
+
+            if key == REMOTE_WAKE_KEY {
+                rearm = true;
+            }
+            else if let Some(queue) = self.multishot.get_mut(&key) {
+                queue.push_back(cqe);
+
+                let waker = match more {
+                    true => self.submissions.get(&key).cloned(),
+                    false => self.submissions.remove(&key)
+                };
+
+                wakeups.extend(waker);
+            }
+            else if let Some(waker) = self.submissions.remove(&key) {
+                debug_assert!(!more, "operation producing several completions wasn't submitted as a stream");
+                self.completions.insert(key, cqe);
+                wakeups.push(waker);
+            }
+            else if let Some(data) = self.orphans.remove(&key) {
+                discard_cqe(self, &*data, cqe);
+
+                if more {
+                    self.orphans.insert(key, data);
+                }
             }
         }
+
+        if rearm {
+            self.arm_remote_wake();
+        }
+
+        self.remote.drain(remote_wakeups);
+    }
+
+    /// Submits a read of the remote wakeups eventfd, which completes whenever another
+    /// thread wakes up one of our tasks
+    fn arm_remote_wake(&mut self) {
+        let fd = Fd(self.remote.eventfd().as_raw_fd());
+        let buf = &mut *self.remote_buf as *mut u64 as *mut u8;
+
+        let sqe = opcode::Read::new(fd, buf, std::mem::size_of::<u64>() as u32)
+            .build()
+            .user_data(REMOTE_WAKE_KEY as u64);
+
+        self.submit_sqe(sqe);
     }
 
+    /// Waits for the cancellation of the IO of dropped futures to finish
+    /// 
+    /// This is called after all tasks have been dropped, once this returns the kernel
+    /// no longer accesses any memory of the dropped tasks.
     pub fn reset(&mut self) {
-        // To get rid of pending IO we drop the current io_uring and
-        // reset to our original state, we don't handle UringErrors
-        // because since by this point `new()` has been called
-        // successfully it is unlikely to return an error now
-        *self = Self::new().unwrap();
+        let mut wakeups = Vec::new();
+        let mut remote_wakeups = Vec::new();
+
+        while !self.orphans.is_empty() {
+            self.wait_for_io(&mut wakeups, &mut remote_wakeups);
+        }
+    }
+
+    /// Checks whether the kernel supports an opcode
+    pub (crate) fn is_supported(&self, code: u8) -> bool {
+        self.probe.is_supported(code)
+    }
+
+    /// Registers buffers with the ring, only one set of buffers can be registered at a time
+    /// 
+    /// # Safety
+    /// The buffers must stay valid until they're unregistered
+    pub (crate) unsafe fn register_buffers(&mut self, iovecs: &[libc::iovec]) -> io::Result<()> {
+        if self.registered_buffers.is_some() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "buffers are already registered with the ring"));
+        }
+
+        self.ring.submitter().register_buffers(iovecs)?;
+        self.registered_buffers = Some(iovecs[0].iov_base as *const u8);
+
+        Ok(())
+    }
+
+    /// Unregisters the buffers starting at `start`, if they're still the registered ones
+    /// 
+    /// Operations already using them keep the buffers pinned until they complete.
+    pub (crate) fn unregister_buffers(&mut self, start: *const u8) {
+        if self.registered_buffers == Some(start) {
+            let _ = self.ring.submitter().unregister_buffers();
+            self.registered_buffers = None;
+        }
+    }
+
+    /// Registers a ring of provided buffers, returning its buffer group ID
+    /// 
+    /// # Safety
+    /// The ring must stay valid until it's unregistered
+    pub (crate) unsafe fn register_buf_ring(&mut self, ring_addr: u64, entries: u16) -> io::Result<u16> {
+        let bgid = match self.free_buf_groups.pop() {
+            Some(bgid) => bgid,
+            None => {
+                let bgid = self.next_buf_group;
+                self.next_buf_group = bgid.checked_add(1)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::OutOfMemory, "too many buffer rings"))?;
+                bgid
+            }
+        };
+
+        if let Err(err) = self.ring.submitter().register_buf_ring_with_flags(ring_addr, entries, bgid, 0) {
+            self.free_buf_groups.push(bgid);
+            return Err(err);
+        }
+
+        Ok(bgid)
+    }
+
+    /// Unregisters a ring of provided buffers, operations already using it keep the buffers they selected
+    pub (crate) fn unregister_buf_ring(&mut self, bgid: u16) {
+        if self.ring.submitter().unregister_buf_ring(bgid).is_ok() {
+            self.free_buf_groups.push(bgid);
+        }
+    }
+
+    /// Reserves a slot in the registered files table for a file which is about to be opened,
+    /// returns false if the table is disabled or full
+    pub (crate) fn reserve_file_slot(&mut self) -> bool {
+        match &mut self.file_slots {
+            Some(slots) if slots.used < slots.count => {
+                slots.used += 1;
+                true
+            },
+
+            _ => false
+        }
+    }
+
+    /// Checks whether the registered files table has slots which aren't reserved
+    pub (crate) fn file_slots_available(&self) -> bool {
+        matches!(&self.file_slots, Some(slots) if slots.used < slots.count)
+    }
+
+    /// Counts a slot the kernel picked for a file which was opened without reserving it,
+    /// the slot is then released like reserved ones
+    pub (crate) fn take_file_slot(&mut self) {
+        if let Some(slots) = &mut self.file_slots {
+            slots.used += 1;
+        }
+    }
+
+    /// Releases a slot reserved with [`reserve_file_slot()`](Self::reserve_file_slot), once the
+    /// file was closed or failed to open
+    pub (crate) fn release_file_slot(&mut self) {
+        if let Some(slots) = &mut self.file_slots {
+            slots.used -= 1;
+        }
     }
 
     pub (crate) fn new_io_key(&mut self) -> IoKey {
         let key = self.io_key_counter;
         self.io_key_counter = key.wrapping_add(1);
 
-        if self.io_key_counter == 0 {
+        if self.io_key_counter == REMOTE_WAKE_KEY {
             self.io_key_counter = 1;
         }
 
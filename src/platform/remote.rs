@@ -0,0 +1,64 @@
+use std::io;
+use std::mem;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::os::fd::{OwnedFd, FromRawFd, AsRawFd};
+use crate::runtime::TaskId;
+
+/// Wakeups coming from other threads
+/// 
+/// Other threads can't touch the thread-local runtime, so they push the woken task's ID
+/// into this queue and then write to the eventfd. The runtime always has a read of the
+/// eventfd armed on the ring, so the write makes [`Platform::wait_for_io`](super::Platform::wait_for_io)
+/// return and the queue then gets drained into the wakeup list.
+pub struct RemoteWakeups {
+    queue: Mutex<Vec<TaskId>>,
+    pending: AtomicBool,
+    eventfd: OwnedFd
+}
+
+impl RemoteWakeups {
+    pub fn new() -> io::Result<Self> {
+        // The eventfd is left blocking, io_uring will arm a poll for it instead of
+        // failing the read with EAGAIN
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            queue: Mutex::new(Vec::new()),
+            pending: AtomicBool::new(false),
+            eventfd: unsafe { OwnedFd::from_raw_fd(fd) }
+        })
+    }
+
+    pub fn eventfd(&self) -> &OwnedFd {
+        &self.eventfd
+    }
+
+    /// Queues a task to be woken up, and notifies the runtime thread if it
+    /// doesn't already have wakeups pending
+    pub fn wake(&self, id: TaskId) {
+        self.queue.lock().unwrap().push(id);
+
+        // The ID must be pushed before the pending flag is set, see `drain()`
+        if !self.pending.swap(true, Ordering::SeqCst) {
+            let val = 1u64;
+
+            unsafe {
+                libc::write(self.eventfd.as_raw_fd(), &val as *const u64 as *const _, mem::size_of::<u64>());
+            }
+        }
+    }
+
+    /// Moves all queued task IDs into the wakeup list
+    pub fn drain(&self, wakeups: &mut Vec<TaskId>) {
+        // Clearing the flag before taking the queue means any ID pushed after we take the
+        // queue will write to the eventfd again, so no wakeup can get lost
+        if self.pending.swap(false, Ordering::SeqCst) {
+            wakeups.append(&mut self.queue.lock().unwrap());
+        }
+    }
+}
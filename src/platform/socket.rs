@@ -1,155 +1,352 @@
 use std::io;
+use std::mem;
 use std::net::{TcpStream, SocketAddr, Shutdown};
 use std::os::fd::{FromRawFd, AsRawFd};
-use io_uring::opcode;
-use io_uring::types::Fd;
-use crate::RUNTIME;
+use std::rc::Rc;
+use io_uring::{opcode, squeue};
+use crate::buf::{IoBuf, IoBufMut, RingShared, ProvidedBuf};
+
+use super::{libc_result_to_std, std_addr_to_libc, MAX_LIBC_SOCKADDR_SIZE, libc_addr_to_std, read_sqe, write_sqe, is_supported, iovecs, iovecs_mut, set_init_vectored, CURRENT_POS};
+use super::uring_fut::{UringFut, UringStream, OpensFd};
+use super::Cqe;
+use super::handle::{Handle, reserve_slot, destination};
+
+/// `IORING_SEND_ZC_REPORT_USAGE`, makes the notification of a zero-copy send report whether the data was copied
+const SEND_ZC_REPORT_USAGE: u16 = 1 << 3;
+
+/// `IORING_NOTIF_USAGE_ZC_COPIED`, set in the notification's result if the kernel copied the data after all
+const NOTIF_USAGE_ZC_COPIED: i32 = i32::MIN;
+
+/// A msghdr along with the iovec and address it points to
+/// 
+/// It is always boxed, so that the pointers stay valid while the kernel uses them
+/// even if the operation's future is moved or dropped.
+struct MsgHdr {
+    msghdr: libc::msghdr,
+    iovec: libc::iovec,
+    addr: [u8; MAX_LIBC_SOCKADDR_SIZE]
+}
 
-use super::{libc_result_to_std, std_addr_to_libc, MAX_LIBC_SOCKADDR_SIZE, libc_addr_to_std};
-use super::uring_fut::UringFut;
+impl MsgHdr {
+    fn new(buf: *mut u8, len: usize, addr: [u8; MAX_LIBC_SOCKADDR_SIZE]) -> Box<Self> {
+        let mut hdr = Box::new(Self {
+            msghdr: unsafe { mem::zeroed() },
+            iovec: libc::iovec { iov_base: buf as *mut _, iov_len: len },
+            addr
+        });
+
+        hdr.msghdr.msg_name = hdr.addr.as_mut_ptr() as *mut _;
+        hdr.msghdr.msg_namelen = hdr.addr.len() as u32;
+        hdr.msghdr.msg_iov = &mut hdr.iovec;
+        hdr.msghdr.msg_iovlen = 1;
+
+        hdr
+    }
+
+    /// Creates a msghdr without an address pointing to several buffers, the iovecs must be kept alive along with it
+    fn vectored(iovecs: &mut [libc::iovec]) -> Box<Self> {
+        let mut hdr = Box::new(Self {
+            msghdr: unsafe { mem::zeroed() },
+            iovec: unsafe { mem::zeroed() },
+            addr: [0u8; MAX_LIBC_SOCKADDR_SIZE]
+        });
+
+        hdr.msghdr.msg_iov = iovecs.as_mut_ptr();
+        hdr.msghdr.msg_iovlen = iovecs.len();
+
+        hdr
+    }
+}
 
-pub async fn socket_create<T: FromRawFd>(ipv6: bool, udp: bool) -> io::Result<T> {
+pub async fn socket_create<T: AsRawFd + FromRawFd>(ipv6: bool, udp: bool) -> io::Result<Handle<T>> {
     let domain = if ipv6 { libc::AF_INET6 } else { libc::AF_INET };
     let socket_type = if udp { libc::SOCK_DGRAM } else { libc::SOCK_STREAM };
     let protocol = if udp { libc::IPPROTO_UDP } else { libc::IPPROTO_TCP };
 
-    let sqe = opcode::Socket::new(domain, socket_type, protocol).build();
-    let res = UringFut::new(sqe).await;
+    let direct = reserve_slot();
+
+    let sqe = opcode::Socket::new(domain, socket_type, protocol)
+        .file_index(destination(direct))
+        .build();
 
-    let fd = libc_result_to_std(res);
+    let (res, op) = UringFut::with_data(sqe, OpensFd::new(direct, Box::new(()))).await;
 
-    fd.map(|fd| unsafe { T::from_raw_fd(fd) })
+    Handle::opened(res, op.into_parts().0).await
 }
 
-pub fn socket_close<T: AsRawFd>(sock: &T) {
-    RUNTIME.with_borrow_mut(|rt| {
-        let sqe = opcode::Close::new(Fd(sock.as_raw_fd()))
+pub async fn socket_connect<T: AsRawFd>(sock: &Handle<T>, addr: &SocketAddr) -> io::Result<()> {
+    let addr = Box::new(std_addr_to_libc(addr));
+    let (fd, flags) = sock.target();
+
+    let sqe = opcode::Connect::new(fd, addr.as_ptr() as *const libc::sockaddr, addr.len() as u32)
+        .build()
+        .flags(flags);
+
+    let (res, _) = UringFut::with_data(sqe, addr).await;
+
+    libc_result_to_std(res).map(|_| ())
+}
+
+pub async fn socket_recv<T: AsRawFd, B: IoBufMut>(sock: &Handle<T>, mut buf: B, peek: bool) -> (io::Result<usize>, B) {
+    let (fd, flags) = sock.target();
+
+    // Registered buffers can only be used through ReadFixed, which can't peek
+    let sqe = match buf.fixed_index().is_some() && !peek {
+        true => read_sqe(fd, buf.fixed_index(), buf.stable_mut_ptr(), buf.bytes_total() as u32, CURRENT_POS),
+
+        false => opcode::Recv::new(fd, buf.stable_mut_ptr(), buf.bytes_total() as u32)
+            .flags(if peek { libc::MSG_PEEK } else { 0 })
             .build()
-            .user_data(0); // IoKey 0 reserved for fd closes
+    };
+
+    let sqe = sqe.flags(flags);
+
+    let (res, mut buf) = UringFut::with_data(sqe, buf).await;
 
-        rt.plat.submit_sqe(sqe);   
+    let res = libc_result_to_std(res).map(|bytes| {
+        unsafe { buf.set_init(bytes as usize) };
+        bytes as usize
     });
+
+    (res, buf)
 }
 
-pub async fn socket_connect<T: AsRawFd>(sock: &T, addr: &SocketAddr) -> io::Result<()> {
-    let addr = std_addr_to_libc(addr);
+pub async fn socket_recv_provided<T: AsRawFd>(sock: &Handle<T>, ring: &Rc<RingShared>) -> io::Result<Option<ProvidedBuf>> {
+    let (fd, flags) = sock.target();
 
-    let sqe = opcode::Connect::new(Fd(sock.as_raw_fd()), addr.as_ptr() as *const libc::sockaddr, addr.len() as u32).build();
-    let res = UringFut::new(sqe).await;
+    let sqe = opcode::Recv::new(fd, std::ptr::null_mut(), ring.buf_size() as u32)
+        .buf_group(ring.bgid())
+        .build()
+        .flags(flags | squeue::Flags::BUFFER_SELECT);
 
-    libc_result_to_std(res).map(|_| ())
+    let (cqe, ring) = UringFut::with_data(sqe, ring.clone()).cqe().await;
+
+    provided_buf(ring, cqe)
 }
 
-pub async fn socket_recv<T: AsRawFd>(sock: &T, buf: &mut [u8], peek: bool) -> io::Result<usize> {
-    let sqe = opcode::Recv::new(Fd(sock.as_raw_fd()), buf.as_mut_ptr(), buf.len() as u32)
-        .flags(if peek { libc::MSG_PEEK } else { 0 })
-        .build();
+/// Creates a multishot recv, which selects a buffer from the ring each time data arrives
+pub fn socket_recv_multi<T: AsRawFd>(sock: &Handle<T>, ring: &Rc<RingShared>) -> UringStream<Rc<RingShared>> {
+    let (fd, flags) = sock.target();
+    let sqe = opcode::RecvMulti::new(fd, ring.bgid()).build().flags(flags);
 
-    let res = UringFut::new(sqe).await;
-    libc_result_to_std(res).map(|bytes| bytes as usize)
+    UringStream::with_data(sqe, ring.clone())
 }
 
-pub async fn socket_recv_from<T: AsRawFd>(sock: &T, buf: &mut [u8], peek: bool) -> io::Result<(usize, SocketAddr)> {
-    // Since a future is always pinned before use, these variable will have
-    // a stable address that we can pass to the kernel without boxing
-    // This approach saves us a heap allocation
-    let mut iovec = libc::iovec {
-        iov_base: buf.as_ptr() as *mut _,
-        iov_len: buf.len()
-    };
+/// Takes the buffer selected by a recv's completion, returns `None` on EOF
+pub fn provided_buf(ring: Rc<RingShared>, cqe: Cqe) -> io::Result<Option<ProvidedBuf>> {
+    // The buffer is taken even on EOF so that it's recycled
+    let buf = cqe.buffer().map(|bid| ProvidedBuf::new(ring, bid, cqe.result.max(0) as usize));
+
+    libc_result_to_std(cqe.result)?;
+
+    match buf {
+        Some(buf) if !buf.is_empty() => Ok(Some(buf)),
+        _ => Ok(None)
+    }
+}
 
+pub async fn socket_recv_from<T: AsRawFd, B: IoBufMut>(sock: &Handle<T>, mut buf: B, peek: bool) -> (io::Result<(usize, SocketAddr)>, B) {
     // Create buffer with sufficient space to hold the largest sockaddr that we're expecting
-    let mut src_addr = [0u8; MAX_LIBC_SOCKADDR_SIZE];
-
-    let mut msghdr = libc::msghdr {
-        msg_name: src_addr.as_mut_ptr() as *mut _,
-        msg_namelen: src_addr.len() as u32,
-        msg_iov: &mut iovec,
-        msg_iovlen: 1,
-        msg_control: std::ptr::null_mut(),
-        msg_controllen: 0,
-        msg_flags: if peek { libc::MSG_PEEK } else { 0 }
-    };
+    let mut msghdr = MsgHdr::new(buf.stable_mut_ptr(), buf.bytes_total(), [0u8; MAX_LIBC_SOCKADDR_SIZE]);
+    let (fd, flags) = sock.target();
+
+    let sqe = opcode::RecvMsg::new(fd, &mut msghdr.msghdr)
+        .flags(if peek { libc::MSG_PEEK as u32 } else { 0 })
+        .build()
+        .flags(flags);
 
-    let sqe = opcode::RecvMsg::new(Fd(sock.as_raw_fd()), &mut msghdr).build();
-    let res = UringFut::new(sqe).await;
+    let (res, (mut buf, msghdr)) = UringFut::with_data(sqe, (buf, msghdr)).await;
 
-    libc_result_to_std(res).map(|bytes| {
-        let src_addr = unsafe { &*(src_addr.as_ptr() as *const _) };
+    let res = libc_result_to_std(res).map(|bytes| {
+        unsafe { buf.set_init(bytes as usize) };
+
+        let src_addr = unsafe { &*(msghdr.addr.as_ptr() as *const _) };
         let src_addr = libc_addr_to_std(src_addr);
 
         (bytes as usize, src_addr)
-    })
+    });
+
+    (res, buf)
 }
 
-pub async fn socket_send<T: AsRawFd>(sock: &T, buf: &[u8]) -> io::Result<usize> {
-    let sqe = opcode::Send::new(Fd(sock.as_raw_fd()), buf.as_ptr(), buf.len() as u32).build();
-    let res = UringFut::new(sqe).await;
+pub async fn socket_recv_vectored<T: AsRawFd, B: IoBufMut>(sock: &Handle<T>, mut bufs: Vec<B>) -> (io::Result<usize>, Vec<B>) {
+    let mut iovecs = iovecs_mut(&mut bufs);
+    let mut msghdr = MsgHdr::vectored(&mut iovecs);
+    let (fd, flags) = sock.target();
+
+    let sqe = opcode::RecvMsg::new(fd, &mut msghdr.msghdr).build().flags(flags);
+    let (res, (mut bufs, ..)) = UringFut::with_data(sqe, (bufs, iovecs, msghdr)).await;
 
-    libc_result_to_std(res).map(|bytes| bytes as usize)
+    let res = libc_result_to_std(res).map(|bytes| {
+        set_init_vectored(&mut bufs, bytes as usize);
+        bytes as usize
+    });
+
+    (res, bufs)
 }
 
-pub async fn socket_send_to<T: AsRawFd>(sock: &T, buf: &[u8], addr: &SocketAddr) -> io::Result<usize> {
-    // A future is always pinned before use, so these will have a static address,
-    // that we can pass to the kernel without boxing.
-    //
-    // Since  these are self referential, we can't use a move closure, so we
-    // use a move async block instead to capture the variables by value and
-    // move them into the async block's state.
-    let mut iovec = libc::iovec {
-        iov_base: buf.as_ptr() as *mut _,
-        iov_len: buf.len()
-    };
+pub async fn socket_send_vectored<T: AsRawFd, B: IoBuf>(sock: &Handle<T>, bufs: Vec<B>) -> (io::Result<usize>, Vec<B>) {
+    let mut iovecs = iovecs(&bufs);
+    let msghdr = MsgHdr::vectored(&mut iovecs);
+    let (fd, flags) = sock.target();
+
+    let sqe = opcode::SendMsg::new(fd, &msghdr.msghdr).build().flags(flags);
+    let (res, (bufs, ..)) = UringFut::with_data(sqe, (bufs, iovecs, msghdr)).await;
+
+    (libc_result_to_std(res).map(|bytes| bytes as usize), bufs)
+}
 
-    let mut addr = std_addr_to_libc(&addr);
+pub async fn socket_send<T: AsRawFd, B: IoBuf>(sock: &Handle<T>, buf: B) -> (io::Result<usize>, B) {
+    let (fd, flags) = sock.target();
 
-    let mut msghdr = libc::msghdr {
-        msg_name: addr.as_mut_ptr() as *mut _,
-        msg_namelen: addr.len() as u32,
-        msg_iov: &mut iovec,
-        msg_iovlen: 1,
-        msg_control: std::ptr::null_mut(),
-        msg_controllen: 0,
-        msg_flags: 0
+    let sqe = match buf.fixed_index() {
+        Some(_) => write_sqe(fd, buf.fixed_index(), buf.stable_ptr(), buf.bytes_init() as u32, CURRENT_POS),
+        None => opcode::Send::new(fd, buf.stable_ptr(), buf.bytes_init() as u32).build()
     };
 
-    let sqe = opcode::SendMsg::new(Fd(sock.as_raw_fd()), &mut msghdr).build();
-    let res = UringFut::new(sqe).await;
+    let sqe = sqe.flags(flags);
+
+    let (res, buf) = UringFut::with_data(sqe, buf).await;
 
-    libc_result_to_std(res).map(|bytes| bytes as usize)
+    (libc_result_to_std(res).map(|bytes| bytes as usize), buf)
 }
 
-pub async fn socket_accept<T: AsRawFd>(sock: &T) -> io::Result<(TcpStream, SocketAddr)> {
+/// Sends the buffer without copying it into the kernel, returning the number of bytes sent and whether
+/// the data actually wasn't copied
+/// 
+/// The buffer is only handed back once the kernel no longer uses it.
+pub async fn socket_send_zc<T: AsRawFd, B: IoBuf>(sock: &Handle<T>, mut buf: B) -> (io::Result<(usize, bool)>, B) {
+    if !is_supported(opcode::SendZc::CODE) {
+        let (res, buf) = socket_send(sock, buf).await;
+        return (res.map(|bytes| (bytes, false)), buf);
+    }
+
+    let (fd, flags) = sock.target();
+    let mut report_usage = true;
+
+    loop {
+        let sqe = opcode::SendZc::new(fd, buf.stable_ptr(), buf.bytes_init() as u32)
+            .buf_index(buf.fixed_index())
+            .zc_flags(if report_usage { SEND_ZC_REPORT_USAGE } else { 0 })
+            .build()
+            .flags(flags);
+
+        let mut op = UringStream::with_data(sqe, buf);
+        let res = send_zc_result(&mut op, report_usage).await;
+        buf = op.into_data().unwrap();
+
+        match res {
+            Err(err) if report_usage && err.raw_os_error() == Some(libc::EINVAL) => report_usage = false,
+            res => return (res, buf)
+        }
+    }
+}
+
+pub async fn socket_send_to_zc<T: AsRawFd, B: IoBuf>(sock: &Handle<T>, mut buf: B, addr: &SocketAddr) -> (io::Result<(usize, bool)>, B) {
+    if !is_supported(opcode::SendMsgZc::CODE) {
+        let (res, buf) = socket_send_to(sock, buf, addr).await;
+        return (res.map(|bytes| (bytes, false)), buf);
+    }
+
+    let mut msghdr = MsgHdr::new(buf.stable_ptr() as *mut _, buf.bytes_init(), std_addr_to_libc(addr));
+    let (fd, flags) = sock.target();
+    let mut report_usage = true;
+
+    loop {
+        let sqe = opcode::SendMsgZc::new(fd, &msghdr.msghdr)
+            .ioprio(if report_usage { SEND_ZC_REPORT_USAGE } else { 0 })
+            .build()
+            .flags(flags);
+
+        let mut op = UringStream::with_data(sqe, (buf, msghdr));
+        let res = send_zc_result(&mut op, report_usage).await;
+        (buf, msghdr) = op.into_data().unwrap();
+
+        match res {
+            Err(err) if report_usage && err.raw_os_error() == Some(libc::EINVAL) => report_usage = false,
+            res => return (res, buf)
+        }
+    }
+}
+
+/// Waits for the result of a zero-copy send, and then for its notification which arrives
+/// once the kernel no longer uses the buffer
+/// 
+/// Whether the data was copied is only known with `report_usage`, which the kernel rejects with `EINVAL`
+/// before Linux 6.2, the send is then retried without it and reported as copied.
+async fn send_zc_result<T>(op: &mut UringStream<T>, report_usage: bool) -> io::Result<(usize, bool)> {
+    let res = op.next().await.expect("zero-copy send produced no completion").result;
+    let mut zero_copy = false;
+
+    while let Some(cqe) = op.next().await {
+        if cqe.notif() && report_usage {
+            zero_copy = cqe.result & NOTIF_USAGE_ZC_COPIED == 0;
+        }
+    }
+
+    libc_result_to_std(res).map(|bytes| (bytes as usize, zero_copy))
+}
+
+pub async fn socket_send_to<T: AsRawFd, B: IoBuf>(sock: &Handle<T>, buf: B, addr: &SocketAddr) -> (io::Result<usize>, B) {
+    let msghdr = MsgHdr::new(buf.stable_ptr() as *mut _, buf.bytes_init(), std_addr_to_libc(addr));
+    let (fd, flags) = sock.target();
+
+    let sqe = opcode::SendMsg::new(fd, &msghdr.msghdr).build().flags(flags);
+    let (res, (buf, _)) = UringFut::with_data(sqe, (buf, msghdr)).await;
+
+    (libc_result_to_std(res).map(|bytes| bytes as usize), buf)
+}
+
+pub async fn socket_accept<T: AsRawFd>(sock: &Handle<T>) -> io::Result<(Handle<TcpStream>, SocketAddr)> {
     // Create buffer with sufficient space to hold the largest sockaddr that we're expecting
-    let mut sockaddr = [0u8; MAX_LIBC_SOCKADDR_SIZE];
-    let mut addrlen = MAX_LIBC_SOCKADDR_SIZE as libc::socklen_t;
+    let mut sockaddr = Box::new(([0u8; MAX_LIBC_SOCKADDR_SIZE], MAX_LIBC_SOCKADDR_SIZE as libc::socklen_t));
+
+    let libc_addr = sockaddr.0.as_mut_ptr() as *mut libc::sockaddr;
+    let addrlen = &mut sockaddr.1 as *mut libc::socklen_t;
+
+    let (fd, flags) = sock.target();
+    let direct = reserve_slot();
 
-    let libc_addr = sockaddr.as_mut_ptr() as *mut libc::sockaddr;
+    let sqe = opcode::Accept::new(fd, libc_addr, addrlen)
+        .file_index(destination(direct))
+        .build()
+        .flags(flags);
 
-    let sqe = opcode::Accept::new(Fd(sock.as_raw_fd()), libc_addr, &mut addrlen).build();
-    let res = UringFut::new(sqe).await;
+    let (res, op) = UringFut::with_data(sqe, OpensFd::new(direct, sockaddr)).await;
+    let (direct, sockaddr) = op.into_parts();
 
-    let fd = libc_result_to_std(res);
+    let stream = Handle::opened(res, direct).await?;
+
+    let sockaddr = sockaddr.downcast::<([u8; MAX_LIBC_SOCKADDR_SIZE], libc::socklen_t)>().unwrap();
+    let peer_addr = unsafe { &*(sockaddr.0.as_ptr() as *const libc::sockaddr) };
+    let peer_addr = libc_addr_to_std(peer_addr);
+
+    Ok((stream, peer_addr))
+}
 
-    fd.map(|fd| {
-        let stream = unsafe { TcpStream::from_raw_fd(fd) };
+/// Creates a multishot accept, which completes with each accepted connection's fd, or its slot in the
+/// registered files table if `direct` is set, see [`multishot_direct()`](super::handle::multishot_direct)
+pub fn socket_accept_multi<T: AsRawFd>(sock: &Handle<T>, direct: bool) -> UringStream<OpensFd> {
+    let (fd, flags) = sock.target();
 
-        let peer_addr = unsafe { &*libc_addr };
-        let peer_addr = libc_addr_to_std(peer_addr);
+    let sqe = opcode::AcceptMulti::new(fd)
+        .allocate_file_index(direct)
+        .build()
+        .flags(flags);
 
-        (stream, peer_addr)
-    })
+    UringStream::with_data(sqe, OpensFd::multishot(direct))
 }
 
-pub async fn socket_shutdown<T: AsRawFd>(sock: &T, how: Shutdown) -> io::Result<()> {
+pub async fn socket_shutdown<T: AsRawFd>(sock: &Handle<T>, how: Shutdown) -> io::Result<()> {
     let how = match how {
         Shutdown::Read => libc::SHUT_RD,
         Shutdown::Write => libc::SHUT_WR,
         Shutdown::Both => libc::SHUT_RDWR
     };
 
-    let sqe = opcode::Shutdown::new(Fd(sock.as_raw_fd()), how).build();
-    let res = UringFut::new(sqe).await;
+    let (fd, flags) = sock.target();
+    let sqe = opcode::Shutdown::new(fd, how).build().flags(flags);
+    let (res, _) = UringFut::new(sqe).await;
 
     libc_result_to_std(res).map(|_| ())
 }
@@ -1,9 +1,84 @@
-use std::future::Future;
+use std::any::Any;
+use std::cell::Cell;
+use std::future::{Future, poll_fn};
+use std::collections::VecDeque;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use io_uring::{squeue, opcode};
+use io_uring::types::Fixed;
 use crate::RUNTIME;
-use super::IoKey;
+use crate::buf::{recycle_buffer, release_ring};
+use super::{IoKey, Cqe, Platform};
+
+/// Data of operations opening a file or socket, which closes what they opened if their completion is discarded
+/// 
+/// Operations opening into the registered files table hold the slot reserved for them, which is released once
+/// the operation is discarded, or once this is dropped if the operation never ran.
+pub (crate) struct OpensFd {
+    // Whether files are opened into the registered files table
+    direct: bool,
+
+    // Multishot operations don't reserve slots, the slots they use are counted once their completions arrive
+    reserved: Cell<bool>,
+    data: Box<dyn Any>
+}
+
+impl OpensFd {
+    /// `direct` is the return value of [`reserve_slot()`](super::handle::reserve_slot), `data` is whatever
+    /// memory the operation points into
+    pub fn new(direct: bool, data: Box<dyn Any>) -> Self {
+        Self { direct, reserved: Cell::new(direct), data }
+    }
+
+    /// Creates the data of a multishot operation, `direct` is the return value of
+    /// [`multishot_direct()`](super::handle::multishot_direct)
+    pub fn multishot(direct: bool) -> Self {
+        Self { direct, reserved: Cell::new(false), data: Box::new(()) }
+    }
+
+    /// Takes back whether the file was opened into the registered files table, which must be passed to
+    /// [`Handle::opened()`](super::handle::Handle::opened) along with the result, and the data
+    pub fn into_parts(mut self) -> (bool, Box<dyn Any>) {
+        self.reserved.set(false);
+        (self.direct, std::mem::replace(&mut self.data, Box::new(())))
+    }
+}
+
+impl Drop for OpensFd {
+    fn drop(&mut self) {
+        if self.reserved.get() {
+            RUNTIME.with_borrow_mut(|rt| rt.plat.release_file_slot());
+        }
+    }
+}
+
+/// Releases whatever a completion nobody is waiting for holds, `data` being the data of its operation
+pub (crate) fn discard_cqe(plat: &mut Platform, data: &dyn Any, cqe: Cqe) {
+    if let Some(bid) = cqe.buffer() {
+        recycle_buffer(data, bid);
+    }
+
+    if !cqe.more() {
+        release_ring(plat, data);
+    }
+
+    if let Some(Some(op)) = data.downcast_ref::<Option<OpensFd>>() {
+        match (op.direct, cqe.result >= 0) {
+            (true, true) => {
+                let sqe = opcode::Close::new(Fixed(cqe.result as u32)).build();
+                plat.submit_sqe(sqe.user_data(0)); // IoKey 0 reserved for closes
+            },
+
+            (false, true) => unsafe { libc::close(cqe.result); },
+            _ => ()
+        }
+
+        // Released here since the data is dropped while the runtime is borrowed
+        if op.reserved.replace(false) {
+            plat.release_file_slot();
+        }
+    }
+}
 
 #[derive(Clone, Copy)]
 enum FutState {
@@ -12,22 +87,44 @@ enum FutState {
     Done
 }
 
-pub (crate) struct UringFut {
+/// Future of a single io_uring operation, resolving to the operation's result along with its data
+/// 
+/// The data holds whatever memory the kernel accesses during the operation (buffers, paths, addresses...).
+/// It is handed back when the operation completes, and if the future is dropped before that, it is kept
+/// alive by the platform until the operation's completion arrives.
+pub (crate) struct UringFut<T: 'static = ()> {
     sqe: squeue::Entry,
-    state: FutState
+    state: FutState,
+    data: Option<T>
 }
 
 impl UringFut {
+    /// Creates a future for an operation which doesn't point to any memory
     pub fn new(sqe: squeue::Entry) -> Self {
-        Self { sqe, state: FutState::NotSubmitted }
+        Self::with_data(sqe, ())
     }
 }
 
+impl<T> UringFut<T> {
+    /// Creates a future for an operation which points into `data`
+    /// 
+    /// All pointers in the sqe must point to heap memory owned by `data`, since `data` is
+    /// moved around after the sqe is built.
+    pub fn with_data(sqe: squeue::Entry, data: T) -> Self {
+        Self { sqe, state: FutState::NotSubmitted, data: Some(data) }
+    }
+}
 
-impl Future for UringFut {
-    type Output = i32;
+// The data is never pinned, only pointed into by the kernel, which is unaffected by moving it
+impl<T> Unpin for UringFut<T> {}
 
-    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+impl<T> UringFut<T> {
+    /// Waits for the operation's completion, including its flags
+    pub async fn cqe(mut self) -> (Cqe, T) {
+        poll_fn(|cx| self.poll_cqe(cx)).await
+    }
+
+    fn poll_cqe(&mut self, cx: &mut Context<'_>) -> Poll<(Cqe, T)> {
         match self.state {
             // sqe not submitted yet
             FutState::NotSubmitted => RUNTIME.with_borrow_mut(|rt| {
@@ -35,7 +132,7 @@ impl Future for UringFut {
                 let sqe = self.sqe.clone().user_data(key as u64);
 
                 rt.plat.submit_sqe(sqe);
-                rt.plat.submissions.insert(key, rt.current_task);
+                rt.plat.submissions.insert(key, cx.waker().clone());
                 self.state = FutState::Submitted(key);
 
                 Poll::Pending
@@ -44,11 +141,22 @@ impl Future for UringFut {
             // sqe submitted, query it
             FutState::Submitted(key) => RUNTIME.with_borrow_mut(|rt| {
                 match rt.plat.completions.remove(&key) {
-                    Some(res) => {
+                    Some(cqe) => {
                         self.state = FutState::Done;
-                        Poll::Ready(res)
+                        Poll::Ready((cqe, self.data.take().unwrap()))
                     },
-                    None => Poll::Pending
+
+                    // Still in flight, we may have been polled by a different waker since
+                    // the last poll (e.g. if we were moved into another task), update it
+                    None => {
+                        if let Some(waker) = rt.plat.submissions.get_mut(&key) {
+                            if !waker.will_wake(cx.waker()) {
+                                *waker = cx.waker().clone();
+                            }
+                        }
+
+                        Poll::Pending
+                    }
                 }
             }),
 
@@ -57,15 +165,146 @@ impl Future for UringFut {
     }
 }
 
-impl Drop for UringFut {
+impl<T> Future for UringFut<T> {
+    type Output = (i32, T);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_cqe(cx).map(|(cqe, data)| (cqe.result, data))
+    }
+}
+
+impl<T> Drop for UringFut<T> {
+    fn drop(&mut self) {
+        if let FutState::Submitted(key) = self.state {
+            let data = self.data.take();
+
+            // Data which is no longer used by the kernel is returned,
+            // so that it's dropped outside of the runtime borrow
+            let data = RUNTIME.with_borrow_mut(|rt| {
+                if rt.plat.submissions.remove(&key).is_some() {
+                    let sqe = opcode::AsyncCancel::new(key as u64).build();
+                    rt.plat.submit_sqe(sqe);
+
+                    // The kernel may still be using the data, keep it alive until the operation completes
+                    let data: Box<dyn Any> = Box::new(data);
+                    rt.plat.orphans.insert(key, data);
+                    None
+                }
+                else {
+                    // Completed but never polled again, discard the completion
+                    if let Some(cqe) = rt.plat.completions.remove(&key) {
+                        discard_cqe(&mut rt.plat, &data, cqe);
+                    }
+
+                    data
+                }
+            });
+
+            drop(data);
+        }
+    }
+}
+
+/// Stream of the completions of an operation producing several of them (multishot operations,
+/// zero-copy sends), which ends after a completion without `IORING_CQE_F_MORE`
+/// 
+/// Like [`UringFut`], the data is kept alive until the operation's last completion arrives.
+pub (crate) struct UringStream<T: 'static = ()> {
+    sqe: squeue::Entry,
+    state: FutState,
+    data: Option<T>
+}
+
+impl<T> UringStream<T> {
+    /// Creates a stream for an operation which points into `data`, see [`UringFut::with_data()`]
+    /// 
+    /// The operation is submitted once the stream is first polled.
+    pub fn with_data(sqe: squeue::Entry, data: T) -> Self {
+        Self { sqe, state: FutState::NotSubmitted, data: Some(data) }
+    }
+
+    /// Waits for the operation's next completion, or returns `None` once its last completion was returned
+    pub async fn next(&mut self) -> Option<Cqe> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Cqe>> {
+        match self.state {
+            FutState::NotSubmitted => RUNTIME.with_borrow_mut(|rt| {
+                let key = rt.plat.new_io_key();
+                let sqe = self.sqe.clone().user_data(key as u64);
+
+                rt.plat.submit_sqe(sqe);
+                rt.plat.submissions.insert(key, cx.waker().clone());
+                rt.plat.multishot.insert(key, VecDeque::new());
+                self.state = FutState::Submitted(key);
+
+                Poll::Pending
+            }),
+
+            FutState::Submitted(key) => RUNTIME.with_borrow_mut(|rt| {
+                let cqe = rt.plat.multishot.get_mut(&key).and_then(VecDeque::pop_front);
+
+                match cqe {
+                    Some(cqe) => {
+                        if !cqe.more() {
+                            rt.plat.multishot.remove(&key);
+                            self.state = FutState::Done;
+                        }
+
+                        Poll::Ready(Some(cqe))
+                    },
+
+                    None => {
+                        if let Some(waker) = rt.plat.submissions.get_mut(&key) {
+                            if !waker.will_wake(cx.waker()) {
+                                *waker = cx.waker().clone();
+                            }
+                        }
+
+                        Poll::Pending
+                    }
+                }
+            }),
+
+            FutState::Done => Poll::Ready(None)
+        }
+    }
+
+    /// Takes back the data once the operation's last completion was returned
+    pub fn into_data(mut self) -> Option<T> {
+        match self.state {
+            FutState::Done => self.data.take(),
+            _ => None
+        }
+    }
+}
+
+impl<T> Drop for UringStream<T> {
     fn drop(&mut self) {
-        if let FutState::Submitted(key) = &self.state {
-            RUNTIME.with_borrow_mut(|rt| {
-                if rt.plat.submissions.remove(key).is_some() {
-                    let sqe = opcode::AsyncCancel::new(*key as u64).build();
-                    rt.plat.submit_sqe(sqe);   
+        if let FutState::Submitted(key) = self.state {
+            let data = self.data.take();
+
+            let data = RUNTIME.with_borrow_mut(|rt| {
+                // Completions which were never returned may hold buffers
+                for cqe in rt.plat.multishot.remove(&key).unwrap_or_default() {
+                    discard_cqe(&mut rt.plat, &data, cqe);
+                }
+
+                if rt.plat.submissions.remove(&key).is_some() {
+                    let sqe = opcode::AsyncCancel::new(key as u64).build();
+                    rt.plat.submit_sqe(sqe);
+
+                    let data: Box<dyn Any> = Box::new(data);
+                    rt.plat.orphans.insert(key, data);
+                    None
+                }
+                else {
+                    data
                 }
             });
+
+            drop(data);
         }
     }
-}
\ No newline at end of file
+}
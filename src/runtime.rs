@@ -2,14 +2,17 @@ use std::mem;
 use std::any::Any;
 use std::pin::Pin;
 use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
 use std::os::fd::AsRawFd;
 
 use nohash::IntMap;
 
 use crate::{
     JoinHandle,
-    platform::Platform,
-    error::UringError,
+    platform::{Platform, RingConfig},
+    error::{UringError, JoinError},
+    waker::TaskWaker,
 };
 
 pub type TaskId = u32;
@@ -24,7 +27,29 @@ impl<T: AsRawFd> From<&T> for SocketHandle {
 }
 
 
-type Task = Pin<Box<dyn Future<Output = Box<dyn Any>>>>;
+/// A spawned task, along with the waker used when polling it
+pub struct Task {
+    future: Pin<Box<dyn Future<Output = Box<dyn Any>>>>,
+    waker: Waker
+}
+
+impl Task {
+    pub fn poll(&mut self) -> Poll<Box<dyn Any>> {
+        self.future.as_mut().poll(&mut Context::from_waker(&self.waker))
+    }
+}
+
+/// What the runtime does when a spawned task panics
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// The panicking task is dropped and awaiting its join handle returns [`JoinError::Panic`],
+    /// other tasks keep running
+    #[default]
+    Isolate,
+
+    /// The process is aborted
+    Abort
+}
 
 pub enum WokenTask {
     Root,
@@ -32,38 +57,42 @@ pub enum WokenTask {
 }
 
 struct JoinHandleInfo {
-    result: Option<Box<dyn Any>>,
-    waiting_task: Option<TaskId>
+    result: Option<Result<Box<dyn Any>, JoinError>>,
+    waiting_task: Option<Waker>
 }
 
 pub struct Runtime {
     task_id_counter: TaskId,
 
     pub current_task: TaskId,
+    current_task_aborted: bool,
     tasks: IntMap<TaskId, Task>,
     join_handles: IntMap<TaskId, JoinHandleInfo>,
     task_wakeups: Vec<TaskId>,
 
+    pub panic_policy: PanicPolicy,
     pub plat: Platform,
 }
 
 impl Runtime {
-    pub fn new() -> Result<Self, UringError> {
-        let plat = Platform::new()?;
+    pub fn new(config: RingConfig, panic_policy: PanicPolicy) -> Result<Self, UringError> {
+        let plat = Platform::new(&config)?;
 
         Ok(Self {
             task_id_counter: 1,
             current_task: 0,
+            current_task_aborted: false,
             tasks: IntMap::default(),
             join_handles: IntMap::default(),
             task_wakeups: vec![0], // We always start with the root task already woken up
+            panic_policy,
             plat
         })
     }
 
     fn new_task_id(&mut self) -> TaskId {
         let id = self.task_id_counter;
-        self.task_id_counter += id.wrapping_add(1);
+        self.task_id_counter = id.wrapping_add(1);
 
         // TaskId 0 is reserved for the root task
         if self.task_id_counter == 0 {
@@ -73,21 +102,41 @@ impl Runtime {
         id
     }
 
+    /// Resets the runtime's state, returning all remaining tasks
+    /// 
+    /// The tasks must be dropped outside of the runtime borrow, and [`Platform::reset()`] called after
+    /// dropping them to wait for their IO to be cancelled.
     pub fn reset(&mut self) -> IntMap<TaskId, Task> {
         self.task_id_counter = 1;
         self.current_task = 0;
+        self.current_task_aborted = false;
         self.join_handles = IntMap::default();
         self.task_wakeups = vec![0];
-        self.plat.reset();
 
         // We replace and transfer task ownership to `run()`, avoiding double borrows of the runtime. 
         // This allows tasks to be dropped in `run()`, ensuring exclusive runtime access for each task,
         // even during IO cancellation.
-        mem::replace(&mut self.tasks, IntMap::default())
+        mem::take(&mut self.tasks)
+    }
+
+    /// Waits for IO to complete, returning the wakers of the operations that completed.
+    /// 
+    /// The wakers are returned instead of being woken here since waking them requires
+    /// borrowing the runtime.
+    pub fn wait_for_io(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        self.plat.wait_for_io(&mut wakers, &mut self.task_wakeups);
+        wakers
     }
 
-    pub fn wait_for_io(&mut self) {
-        self.plat.wait_for_io(&mut self.task_wakeups)
+    /// Creates a waker for the task with the given ID
+    pub fn task_waker(&self, id: TaskId) -> Waker {
+        Waker::from(Arc::new(TaskWaker::new(id, self.plat.remote.clone())))
+    }
+
+    /// Places a task into the wakeup list so it gets polled again
+    pub fn wake_task(&mut self, id: TaskId) {
+        self.task_wakeups.push(id);
     }
 }
 
@@ -109,7 +158,9 @@ impl Runtime {
 
         // Place the task in the task list, create it's join handle, and also place in
         // the wakeup list to give it an initial poll
-        self.tasks.insert(id, Box::pin(wrapped_task));
+        let task = Task { future: Box::pin(wrapped_task), waker: self.task_waker(id) };
+
+        self.tasks.insert(id, task);
         self.join_handles.insert(id, JoinHandleInfo { result: None, waiting_task: None });
         self.task_wakeups.push(id);
 
@@ -119,15 +170,15 @@ impl Runtime {
     /// Gets a woken up task from the wakeup list
     /// Returns `None` if there are no more woken up tasks.
     /// 
-    /// If the task is the root task, returns `WokenTask::RootTask`, otherwise returns
-    /// `WokenTask::ChildTask(task)`.
+    /// If the task is the root task, returns `WokenTask::Root`, otherwise returns
+    /// `WokenTask::Child(task)`.
     pub fn get_woken_task(&mut self) -> Option<WokenTask> {
 
         loop {
             let id = self.task_wakeups.pop()?;
             self.current_task = id;
+            self.current_task_aborted = false;
 
-            self.current_task = id;
             if id == 0 {
                 return Some(WokenTask::Root);
             }
@@ -146,27 +197,85 @@ impl Runtime {
 
     /// Marks the current task as finished and stores its result into its join handle.
     /// 
-    /// Also places the ID of the task waiting on the current task's join handle into
-    /// the wakeup list
-    pub fn task_finished(&mut self, res: Box<dyn Any>) {
+    /// Returns the waker of the task waiting on the current task's join handle, if any.
+    /// It must be woken after the runtime borrow is released.
+    pub fn task_finished(&mut self, res: Box<dyn Any>) -> Option<Waker> {
+        // The task aborted itself while being polled, it already has its result
+        if self.current_task_aborted {
+            return None;
+        }
+
         match self.join_handles.get_mut(&self.current_task) {
-            // Write result into join handle and wake up it's waiting task, if any
+            // Write result into join handle and hand back it's waiting task's waker, if any
             Some(handle) => {
-                handle.result = Some(res);
-
-                if let Some(id) = handle.waiting_task {
-                    self.task_wakeups.push(id);
-                }
+                handle.result = Some(Ok(res));
+                handle.waiting_task.take()
             },
 
             // Join handle dropped, discard result
-            None => ()
+            None => None
+        }
+    }
+
+    /// Marks the current task as having panicked and stores the panic's payload into its join handle.
+    /// 
+    /// Returns the waker of the task waiting on the current task's join handle, if any.
+    pub fn task_panicked(&mut self, payload: Box<dyn Any + Send>) -> Option<Waker> {
+        if self.current_task_aborted {
+            return None;
+        }
+
+        match self.join_handles.get_mut(&self.current_task) {
+            Some(handle) => {
+                handle.result = Some(Err(JoinError::Panic(payload)));
+                handle.waiting_task.take()
+            },
+            None => None
         }
     }
 
     /// Returns a task to the task list
-    pub fn return_task(&mut self, task: Task) {
+    /// 
+    /// If the task aborted itself while it was being polled, it is handed back instead
+    /// so that it can be dropped outside of the runtime borrow.
+    pub fn return_task(&mut self, task: Task) -> Option<Task> {
+        if self.current_task_aborted {
+            return Some(task);
+        }
+
         self.tasks.insert(self.current_task, task);
+        None
+    }
+
+    /// Aborts a task, removing it from the task list and resolving its join handle
+    /// with [`JoinError::Cancelled`]
+    /// 
+    /// Returns the removed task, which must be dropped outside of the runtime borrow
+    /// since dropping it cancels its pending IO, and the waker of the task waiting on
+    /// its join handle, if any.
+    pub fn abort_task(&mut self, id: TaskId) -> (Option<Task>, Option<Waker>) {
+        let task = self.tasks.remove(&id);
+
+        // The task is neither in the task list nor being polled, so it has already finished
+        if task.is_none() && id != self.current_task {
+            return (None, None);
+        }
+
+        // The task is aborting itself, it is currently being polled and
+        // will be dropped by `return_task()` once its poll returns
+        if id == self.current_task {
+            self.current_task_aborted = true;
+        }
+
+        let waker = match self.join_handles.get_mut(&id) {
+            Some(handle) if handle.result.is_none() => {
+                handle.result = Some(Err(JoinError::Cancelled));
+                handle.waiting_task.take()
+            },
+            _ => None
+        };
+
+        (task, waker)
     }
 }
 
@@ -175,7 +284,7 @@ impl Runtime {
 
     /// Tries to retrieve a join handle's result and remove it from the list if it's done
     /// Returns `None if the join handle is not done yet
-    pub fn pop_join_handle_result(&mut self, id: TaskId) -> Option<Box<dyn Any>> {
+    pub fn pop_join_handle_result(&mut self, id: TaskId) -> Option<Result<Box<dyn Any>, JoinError>> {
         let info = self.join_handles.remove(&id).expect("Join handle info not found");
 
         if let Some(res) = info.result {
@@ -188,12 +297,16 @@ impl Runtime {
     }
 
 
-    /// Registers a task to be woken up when the task with the given ID completes.
+    /// Registers a waker to be woken up when the task with the given ID completes.
     /// 
-    /// This is called when a task is spawned and a join handle is created for it.
-    pub fn register_join_handle_wakeup(&mut self, id: TaskId) {
+    /// This is called whenever a join handle is polled and the task is not done yet.
+    pub fn register_join_handle_wakeup(&mut self, id: TaskId, waker: &Waker) {
         let info = self.join_handles.get_mut(&id).expect("Join handle info not found");
-        info.waiting_task = Some(self.current_task);
+
+        match &info.waiting_task {
+            Some(waiting) if waiting.will_wake(waker) => (),
+            _ => info.waiting_task = Some(waker.clone())
+        }
     }
 
     /// Drops a join handle - this is called when a join handle is dropped, and is used to
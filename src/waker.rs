@@ -0,0 +1,48 @@
+use std::sync::Arc;
+use std::task::Wake;
+use std::thread::{self, ThreadId};
+
+use crate::{RUNTIME, runtime::TaskId, platform::RemoteWakeups};
+
+/// Waker of a task, which places the task's ID into the runtime's wakeup list
+/// 
+/// When woken from the runtime's own thread the ID is pushed directly into the wakeup list,
+/// otherwise it goes through the runtime's [`RemoteWakeups`], which interrupts the runtime
+/// if it is blocked waiting for IO.
+pub(crate) struct TaskWaker {
+    id: TaskId,
+    thread: ThreadId,
+    remote: Arc<RemoteWakeups>
+}
+
+impl TaskWaker {
+    pub fn new(id: TaskId, remote: Arc<RemoteWakeups>) -> Self {
+        Self { id, thread: thread::current().id(), remote }
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if thread::current().id() == self.thread {
+            // The runtime may be borrowed if we're woken from inside of it,
+            // in this case we take the remote path, which doesn't need to borrow it
+            let woken = RUNTIME.with(|rt| match rt.try_borrow_mut() {
+                Ok(mut rt) => {
+                    rt.wake_task(self.id);
+                    true
+                },
+                Err(_) => false
+            });
+
+            if woken {
+                return;
+            }
+        }
+
+        self.remote.wake(self.id);
+    }
+}